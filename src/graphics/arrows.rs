@@ -0,0 +1,146 @@
+use std::{f32::consts::TAU, ffi::CString};
+
+use crate::graphics::color::*;
+use crate::graphics::objects::*;
+
+use super::camera::Camera;
+
+// One arrow: its tail, the vector from its tail to its tip, and its color.
+#[derive(Clone, Copy)]
+pub struct Arrow {
+    pub position: glm::Vec3,
+    pub vector: glm::Vec3,
+    pub color: Color,
+}
+
+// Floats per arrow in the instance buffer: position, vector and color.
+const INSTANCE_FLOATS: usize = 10;
+
+const SIDES: u32 = 12;
+const SHAFT_RADIUS: f32 = 0.04;
+const HEAD_RADIUS: f32 = 0.1;
+const HEAD_START: f32 = 0.7;
+
+// Draws any number of arrows with a single instanced draw call. The GPU holds one arrow mesh of unit
+// length along +y, and each instance turns, scales and moves it in the vertex shader.
+pub struct ArrowGlyphs {
+    program: Program,
+    vbo: Vbo,
+    instances: Vbo,
+    vao: Vao,
+    ibo: Ibo,
+    indices: u32,
+    count: u32,
+    u_world_to_screen: Uniform,
+    u_lighting: Uniform,
+}
+
+impl ArrowGlyphs {
+    pub fn new(arrows: &[Arrow]) -> Result<Self, String> {
+        let vert_shader = Shader::from_source(&CString::new(include_str!("./graphics3darrow.vert")).unwrap(), gl::VERTEX_SHADER)?;
+        let frag_shader = Shader::from_source(&CString::new(include_str!("./graphics3d.frag")).unwrap(), gl::FRAGMENT_SHADER)?;
+
+        let program = Program::from_shaders(&[&vert_shader, &frag_shader])?;
+        program.set();
+
+        let (vertices, indices) = arrow_mesh();
+
+        let mut instance_data = Vec::with_capacity(arrows.len() * INSTANCE_FLOATS);
+        for arrow in arrows {
+            instance_data.extend([arrow.position.x, arrow.position.y, arrow.position.z]);
+            instance_data.extend([arrow.vector.x, arrow.vector.y, arrow.vector.z]);
+            instance_data.extend([arrow.color.r, arrow.color.g, arrow.color.b, arrow.color.a]);
+        }
+
+        let vbo = Vbo::new();
+        vbo.bind();
+        let instances = Vbo::new();
+        let vao = Vao::new_instanced(
+            &[VertexArrayElement::Floats { count: 3, normalized: false }, VertexArrayElement::Floats { count: 3, normalized: false }],
+            &instances,
+            &[
+                VertexArrayElement::Floats { count: 3, normalized: false },
+                VertexArrayElement::Floats { count: 3, normalized: false },
+                VertexArrayElement::Floats { count: 4, normalized: false },
+            ],
+        );
+        vao.bind();
+        let ibo = Ibo::new();
+        vbo.set(&vertices);
+        instances.set(&instance_data);
+        ibo.set(&indices);
+
+        let u_world_to_screen = Uniform::new(&program, "u_world_to_screen")?;
+        let u_lighting = Uniform::new(&program, "u_lighting")?;
+
+        Ok(ArrowGlyphs {
+            program,
+            vbo,
+            instances,
+            vao,
+            ibo,
+            indices: indices.len() as u32,
+            count: arrows.len() as u32,
+            u_world_to_screen,
+            u_lighting,
+        })
+    }
+
+    pub fn render(&self, camera: &Camera) {
+        self.program.set();
+
+        self.u_world_to_screen.set_mat4(camera.matrix());
+        self.u_lighting.set_vec3(glm::Vec3::new(0.0, -1.0, 1.0));
+
+        self.vao.bind();
+        self.vbo.bind();
+        self.instances.bind();
+        self.ibo.bind();
+
+        unsafe {
+            gl::DrawElementsInstanced(gl::TRIANGLES, self.indices as gl::types::GLsizei, gl::UNSIGNED_INT, std::ptr::null(), self.count as gl::types::GLsizei);
+        }
+    }
+}
+
+// A cylinder for the shaft and a cone for the head, each closed off at the bottom, as interleaved
+// positions and normals.
+fn arrow_mesh() -> (Vec<f32>, Vec<u32>) {
+    let mut vertices = Vec::new();
+    let mut indices = Vec::new();
+    let vertex = |vertices: &mut Vec<f32>, position: glm::Vec3, normal: glm::Vec3| {
+        vertices.extend([position.x, position.y, position.z, normal.x, normal.y, normal.z]);
+        (vertices.len() / 6 - 1) as u32
+    };
+    let around = |side: u32| {
+        let (sin, cos) = (side as f32 / SIDES as f32 * TAU).sin_cos();
+        glm::Vec3::new(cos, 0.0, -sin)
+    };
+
+    // Rings of the shaft and the head, wound so that the outside faces outwards.
+    let cone_slope = glm::Vec2::new(1.0 - HEAD_START, HEAD_RADIUS).normalize();
+    for (radius, bottom, top, normal_y, top_radius) in [(SHAFT_RADIUS, 0.0, HEAD_START, 0.0, SHAFT_RADIUS), (HEAD_RADIUS, HEAD_START, 1.0, cone_slope.y, 0.0)] {
+        let first = (vertices.len() / 6) as u32;
+        let horizontal = (1.0 - normal_y * normal_y).sqrt();
+        for side in 0..SIDES {
+            let direction = around(side);
+            let normal = direction * horizontal + glm::Vec3::y() * normal_y;
+            vertex(&mut vertices, direction * radius + glm::Vec3::y() * bottom, normal);
+            vertex(&mut vertices, direction * top_radius + glm::Vec3::y() * top, normal);
+        }
+        for side in 0..SIDES {
+            let next = (side + 1) % SIDES;
+            let (b0, t0, b1, t1) = (first + 2 * side, first + 2 * side + 1, first + 2 * next, first + 2 * next + 1);
+            indices.extend([b0, b1, t1, t1, t0, b0]);
+        }
+
+        // The disc underneath, facing down.
+        let center = vertex(&mut vertices, glm::Vec3::y() * bottom, -glm::Vec3::y());
+        let rim: Vec<u32> = (0..SIDES).map(|side| vertex(&mut vertices, around(side) * radius + glm::Vec3::y() * bottom, -glm::Vec3::y())).collect();
+        for side in 0..SIDES {
+            indices.extend([center, rim[((side + 1) % SIDES) as usize], rim[side as usize]]);
+        }
+    }
+
+    (vertices, indices)
+}
@@ -96,6 +96,13 @@ impl Camera {
         self.focus += glm::rotate_y_vec3(&(movement * self.distance * 0.02), self.horizontal_angle);
     }
 
+    // The directions in the world that point right and up on the screen.
+    pub fn screen_axes(&self) -> (glm::Vec3, glm::Vec3) {
+        let rotation = glm::mat4_to_mat3(&(glm::rotation(self.vertical_angle, &glm::Vec3::x()) * glm::rotation(-self.horizontal_angle, &glm::Vec3::y())));
+        let inverse = rotation.transpose();
+        (inverse * glm::Vec3::x(), inverse * glm::Vec3::y())
+    }
+
     pub fn matrix(&self) -> glm::Mat4 {
         let position = glm::Vec3::z() * -self.distance;
         let position = glm::rotate_x_vec3(&position, -self.vertical_angle);
@@ -1,3 +1,7 @@
+use std::f64::consts::TAU;
+
+use crate::math::complex::Complex;
+
 #[derive(Clone, Copy)]
 pub struct Color {
     pub r: f32,
@@ -33,6 +37,45 @@ impl Color {
         }
     }
 
+    // Maps 0..1 from blue through green to red, for coloring by a scalar.
+    pub fn from_colormap(t: f32) -> Color {
+        Color::from_hsv(0.7 * (1.0 - t.clamp(0.0, 1.0)), 0.8, 1.0)
+    }
+
+    // Domain coloring of a complex value: the hue follows arg(w) and the brightness repeats every
+    // time |w| doubles, so rings crowd together around zeros and poles. Zeros fade to black and poles
+    // to white.
+    pub fn from_complex(w: Complex) -> Color {
+        let r = w.abs();
+        if r.is_nan() {
+            return Color::from_rgb(0.5, 0.5, 0.5);
+        } else if r == 0.0 {
+            return Color::from_rgb(0.0, 0.0, 0.0);
+        } else if r.is_infinite() {
+            return Color::from_rgb(1.0, 1.0, 1.0);
+        }
+
+        let hue = Color::arg_hue(w);
+        let ring = r.log2().rem_euclid(1.0) as f32;
+        let r = r as f32;
+        let zero = r / (r + 0.05);
+        let pole = 1.0 / (1.0 + 0.02 * r);
+        Color::from_hsv(hue, pole, ((0.6 + 0.4 * ring) * zero).max(1.0 - pole))
+    }
+
+    // A fully saturated color whose hue follows arg(w).
+    pub fn from_arg(w: Complex) -> Color {
+        if w.is_nan() {
+            return Color::from_rgb(0.5, 0.5, 0.5);
+        }
+        Color::from_hsv(Color::arg_hue(w), 0.8, 1.0)
+    }
+
+    fn arg_hue(w: Complex) -> f32 {
+        // The cast can round values just below 1 up to 1, which is the same hue as 0.
+        (w.arg() / TAU).rem_euclid(1.0) as f32 % 1.0
+    }
+
     pub fn to_hsv(self) -> (f32, f32, f32) {
         Color::rgb_to_hsv(self.r, self.g, self.b)
     }
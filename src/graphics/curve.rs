@@ -0,0 +1,267 @@
+use crate::math::expr::Expr;
+
+use super::{color::Color, graphics3d::Graphics3D};
+
+// How the cross-section of a tube is oriented along the curve.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Frames {
+    // Follows the curvature, so the tube twists along with the curve and flips where it straightens out.
+    Frenet,
+    // Turns as little as possible from one point to the next.
+    RotationMinimizing,
+}
+
+impl Frames {
+    pub fn from_name(name: &str) -> Result<Frames, String> {
+        match name {
+            "frenet" => Ok(Frames::Frenet),
+            "rmf" | "rotation-minimizing" => Ok(Frames::RotationMinimizing),
+            _ => Err(format!("unknown frames '{}', expected frenet or rmf", name)),
+        }
+    }
+}
+
+// A curve through 3D space as a polyline, with a value at each point to color it by, like the parameter
+// or the speed. Non-finite points split it into pieces.
+pub struct Curve {
+    pub points: Vec<glm::Vec3>,
+    pub values: Vec<f64>,
+}
+
+impl Curve {
+    // Samples r(t) = (x(t), y(t), z(t)) at `steps` + 1 evenly spaced values of t.
+    pub fn parametric(exprs: [&Expr; 3], t_min: f32, t_max: f32, steps: u32) -> Result<Curve, String> {
+        let values: Vec<f64> = (0..=steps).map(|i| t_min as f64 + (t_max - t_min) as f64 * i as f64 / steps as f64).collect();
+        let mut coordinates = [vec![0.0; values.len()], vec![0.0; values.len()], vec![0.0; values.len()]];
+        for (expr, coordinate) in exprs.iter().zip(&mut coordinates) {
+            expr.compile(&["t"])?.eval_slice(&[&values], coordinate);
+        }
+        let [xs, ys, zs] = coordinates;
+        let points = (0..values.len()).map(|k| glm::Vec3::new(xs[k] as f32, ys[k] as f32, zs[k] as f32)).collect();
+        Ok(Curve { points, values })
+    }
+
+    // Colors from the colormap, spread over the range of the values.
+    pub fn colors(&self) -> Vec<Color> {
+        let (lo, hi) = self.values.iter().filter(|v| v.is_finite()).fold((f64::INFINITY, f64::NEG_INFINITY), |(lo, hi), &v| (lo.min(v), hi.max(v)));
+        self.colors_between(lo, hi)
+    }
+
+    // Colors from the colormap with `lo` and `hi` at its ends, to color several curves the same way.
+    pub fn colors_between(&self, lo: f64, hi: f64) -> Vec<Color> {
+        self.values.iter().map(|&v| Color::from_colormap(if hi > lo { ((v - lo) / (hi - lo)) as f32 } else { 0.5 })).collect()
+    }
+
+    pub fn lines(&self, graphics: &mut Graphics3D, colors: &[Color]) {
+        graphics.line_strip(&self.points, colors);
+    }
+
+    // Sweeps a circle of `radius` along the curve, with `sides` vertices around each ring.
+    pub fn tube(&self, graphics: &mut Graphics3D, colors: &[Color], radius: f32, sides: usize, frames: Frames) {
+        for piece in self.pieces() {
+            let points: Vec<glm::Vec3> = piece.clone().map(|k| self.points[k]).collect();
+            // The tube around a closed curve joins up with itself instead of leaving a seam.
+            let closed = is_closed(&points);
+            let points = if closed { &points[..points.len() - 1] } else { &points[..] };
+            let Some(normals) = ring_normals(points, closed, frames) else {
+                continue;
+            };
+
+            let first = graphics.vertices;
+            for (i, (point, (normal, binormal))) in points.iter().zip(normals).enumerate() {
+                for side in 0..sides {
+                    let (sin, cos) = (side as f32 / sides as f32 * std::f32::consts::TAU).sin_cos();
+                    let direction = normal * cos + binormal * sin;
+                    graphics.vertex(point + direction * radius, colors[piece.start + i], direction);
+                }
+            }
+
+            let rings = if closed { points.len() } else { points.len() - 1 };
+            let id = |ring: usize, side: usize| first + ((ring % points.len()) * sides + side % sides) as u32;
+            for ring in 0..rings {
+                for side in 0..sides {
+                    let (a, b, c, d) = (id(ring, side), id(ring, side + 1), id(ring + 1, side), id(ring + 1, side + 1));
+                    graphics.index_buffer.extend([a, b, d, d, c, a]);
+                }
+            }
+        }
+    }
+
+    // The ranges of consecutive finite points, leaving out single points.
+    fn pieces(&self) -> Vec<std::ops::Range<usize>> {
+        let finite = |k: usize| self.points[k].iter().all(|c| c.is_finite());
+        let mut pieces = Vec::new();
+        let mut start = 0;
+        for k in 0..=self.points.len() {
+            if k == self.points.len() || !finite(k) {
+                if k > start + 1 {
+                    pieces.push(start..k);
+                }
+                start = k + 1;
+            }
+        }
+        pieces
+    }
+}
+
+// A closed curve, like a knot, repeats its first point at the end.
+fn is_closed(points: &[glm::Vec3]) -> bool {
+    let extent = points.iter().fold(1.0f32, |extent, p| extent.max(p.amax()));
+    points.len() > 3 && (points[0] - points[points.len() - 1]).norm() < 1e-5 * extent
+}
+
+// The normal and binormal at each point, which the cross-section of the tube is spanned by. None if the
+// curve doesn't go anywhere.
+fn ring_normals(points: &[glm::Vec3], closed: bool, frames: Frames) -> Option<Vec<(glm::Vec3, glm::Vec3)>> {
+    let n = points.len();
+    let at = |i: isize| if closed { points[i.rem_euclid(n as isize) as usize] } else { points[i.clamp(0, n as isize - 1) as usize] };
+    let tangents: Vec<Option<glm::Vec3>> = (0..n as isize).map(|i| usable(at(i + 1) - at(i - 1))).collect();
+    // Repeated points have no tangent of their own, they borrow the closest one before them, or after them at the start.
+    let first = tangents.iter().flatten().next().copied()?;
+    let mut previous = first;
+    let tangents: Vec<glm::Vec3> = tangents
+        .into_iter()
+        .map(|tangent| {
+            previous = tangent.unwrap_or(previous);
+            previous
+        })
+        .collect();
+
+    let mut normals = Vec::with_capacity(n);
+    match frames {
+        Frames::Frenet => {
+            // The principal normal points along the part of the second difference across the tangent.
+            // Where the curve is straight it has none, and the last one is carried over.
+            let mut previous: Option<glm::Vec3> = None;
+            let mut curvatures: Vec<Option<glm::Vec3>> = Vec::with_capacity(n);
+            for (i, tangent) in tangents.iter().enumerate() {
+                let i = i as isize;
+                let bend = at(i + 1) - at(i) * 2.0 + at(i - 1);
+                let normal = usable(bend - tangent * bend.dot(tangent)).filter(|_| closed || (i > 0 && i < n as isize - 1));
+                curvatures.push(normal);
+            }
+            let fallback = curvatures.iter().flatten().next().copied().unwrap_or_else(|| perpendicular(&tangents[0]));
+            for (tangent, normal) in tangents.iter().zip(curvatures) {
+                let normal = normal.or(previous).unwrap_or(fallback);
+                let normal = usable(normal - tangent * normal.dot(tangent)).unwrap_or_else(|| perpendicular(tangent));
+                previous = Some(normal);
+                normals.push(normal);
+            }
+        }
+        Frames::RotationMinimizing => {
+            // The double reflection method: reflecting the frame in the plane halfway between two points,
+            // and then in the one that lines the reflected tangent up with the next tangent.
+            normals.push(perpendicular(&tangents[0]));
+            let steps = if closed { n } else { n - 1 };
+            for i in 0..steps {
+                let (next, normal, tangent) = ((i + 1) % n, normals[i], tangents[i]);
+                let reflect = |v: glm::Vec3, axis: glm::Vec3| {
+                    let length = axis.dot(&axis);
+                    if length > 1e-12 { v - axis * (2.0 * axis.dot(&v) / length) } else { v }
+                };
+                let step = points[next] - points[i];
+                let (normal, tangent) = (reflect(normal, step), reflect(tangent, step));
+                let normal = reflect(normal, tangents[next] - tangent);
+                let normal = usable(normal - tangents[next] * normal.dot(&tangents[next])).unwrap_or_else(|| perpendicular(&tangents[next]));
+                if next == 0 {
+                    // Going once around a closed curve generally leaves the frame turned by some angle.
+                    // Spreading that turn evenly along the curve makes the ends meet.
+                    let angle = normal.cross(&normals[0]).dot(&tangents[0]).atan2(normal.dot(&normals[0]));
+                    for (k, (normal, tangent)) in normals.iter_mut().zip(&tangents).enumerate() {
+                        *normal = glm::rotate_vec3(normal, angle * k as f32 / n as f32, tangent);
+                    }
+                } else {
+                    normals.push(normal);
+                }
+            }
+        }
+    }
+
+    Some(normals.into_iter().zip(&tangents).map(|(normal, tangent)| (normal, tangent.cross(&normal))).collect())
+}
+
+fn usable(v: glm::Vec3) -> Option<glm::Vec3> {
+    let length = v.norm();
+    (length.is_finite() && length > 1e-12).then(|| v / length)
+}
+
+// Any unit vector perpendicular to `v`.
+fn perpendicular(v: &glm::Vec3) -> glm::Vec3 {
+    let axis = if v.x.abs() < 0.9 { glm::Vec3::x() } else { glm::Vec3::y() };
+    glm::normalize(&v.cross(&axis))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn curve(x: &str, y: &str, z: &str, t_max: f32, steps: u32) -> Vec<glm::Vec3> {
+        let exprs = [x, y, z].map(|source| Expr::parse(source).unwrap());
+        Curve::parametric([&exprs[0], &exprs[1], &exprs[2]], 0.0, t_max, steps).unwrap().points
+    }
+
+    // The frames of a curve the way `tube` builds them, leaving out the repeated end of a closed one.
+    fn frames(points: &[glm::Vec3], frames: Frames) -> (Vec<glm::Vec3>, Vec<glm::Vec3>) {
+        let closed = is_closed(points);
+        let points = if closed { &points[..points.len() - 1] } else { points };
+        let normals = ring_normals(points, closed, frames).unwrap().into_iter().map(|(normal, _)| normal).collect();
+        let tangents = (0..points.len())
+            .map(|i| {
+                let (before, after) = if closed { ((i + points.len() - 1) % points.len(), (i + 1) % points.len()) } else { (i.saturating_sub(1), (i + 1).min(points.len() - 1)) };
+                glm::normalize(&(points[after] - points[before]))
+            })
+            .collect();
+        (normals, tangents)
+    }
+
+    fn trefoil() -> Vec<glm::Vec3> {
+        curve("sin(t) + 2sin(2t)", "cos(t) - 2cos(2t)", "-sin(3t)", std::f32::consts::TAU, 300)
+    }
+
+    #[test]
+    fn detects_closed_curves() {
+        assert!(is_closed(&curve("cos(t)", "sin(t)", "0", std::f32::consts::TAU, 64)));
+        assert!(is_closed(&trefoil()));
+        assert!(!is_closed(&curve("cos(t)", "sin(t)", "0", 6.0, 64)));
+        assert!(!is_closed(&curve("t", "0", "0", 1.0, 2)));
+    }
+
+    #[test]
+    fn rotation_minimizing_frames_meet_at_the_seam() {
+        for points in [curve("cos(t)", "sin(t)", "0", std::f32::consts::TAU, 64), trefoil()] {
+            let (normals, _) = frames(&points, Frames::RotationMinimizing);
+            let turn = |a: usize, b: usize| normals[a].dot(&normals[b]).clamp(-1.0, 1.0).acos();
+            let largest = (1..normals.len()).map(|k| turn(k - 1, k)).fold(0.0, f32::max);
+            assert!(turn(normals.len() - 1, 0) <= largest * 1.5 + 1e-4, "the seam turns by {}, other steps by at most {}", turn(normals.len() - 1, 0), largest);
+        }
+    }
+
+    #[test]
+    fn normals_are_unit_and_perpendicular() {
+        let helix = curve("cos(t)", "sin(t)", "t / 4", 20.0, 200);
+        for points in [trefoil(), helix] {
+            for kind in [Frames::Frenet, Frames::RotationMinimizing] {
+                let (normals, tangents) = frames(&points, kind);
+                for (normal, tangent) in normals.iter().zip(&tangents) {
+                    assert!((normal.norm() - 1.0).abs() < 1e-4, "{:?}", kind);
+                    assert!(normal.dot(tangent).abs() < 0.05, "{:?}", kind);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn straight_pieces_have_normals() {
+        let line = curve("t", "2t", "-t", 1.0, 10);
+        // Straight, then bending, so that Frenet frames carry the first normal back to the straight part.
+        let hook = curve("t", "max(t - 1, 0)^2", "0", 2.0, 20);
+        let repeated = vec![glm::Vec3::new(1.0, 2.0, 3.0), glm::Vec3::new(1.0, 2.0, 3.0), glm::Vec3::new(2.0, 2.0, 3.0)];
+        for points in [line, hook, repeated] {
+            for kind in [Frames::Frenet, Frames::RotationMinimizing] {
+                let (normals, _) = frames(&points, kind);
+                assert!(normals.iter().all(|normal| normal.iter().all(|c| c.is_finite()) && (normal.norm() - 1.0).abs() < 1e-4), "{:?}", kind);
+            }
+        }
+        assert!(ring_normals(&[glm::Vec3::zeros(); 4], false, Frames::Frenet).is_none());
+    }
+}
@@ -0,0 +1,214 @@
+use std::collections::VecDeque;
+
+use sdl2::{event::Event, keyboard::Keycode};
+
+use crate::math::{expr::Expr, flow::VectorField, isosurface::SampleGrid};
+
+use super::{
+    camera::Camera,
+    color::Color,
+    curve::{Curve, Frames},
+    graphics3d::Graphics3D,
+};
+
+// A segment along one of the axes that streamlines and particles start from. Dragging with the right
+// mouse button moves it in the plane of the screen, R turns it to the next axis, and , and . shorten
+// and lengthen it.
+pub struct SeedRake {
+    center: glm::Vec3,
+    axis: usize,
+    half_length: f32,
+    count: usize,
+    changed: bool,
+}
+
+impl SeedRake {
+    pub fn new(center: glm::Vec3, half_length: f32, count: usize) -> Self {
+        SeedRake { center, axis: 0, half_length, count: count.max(1), changed: true }
+    }
+
+    pub fn seeds(&self) -> Vec<[f64; 3]> {
+        (0..self.count)
+            .map(|i| {
+                let t = if self.count == 1 { 0.0 } else { i as f32 / (self.count - 1) as f32 * 2.0 - 1.0 };
+                let mut seed = self.center;
+                seed[self.axis] += t * self.half_length;
+                [seed.x as f64, seed.y as f64, seed.z as f64]
+            })
+            .collect()
+    }
+
+    pub fn process_event(&mut self, event: &Event, camera: &Camera) {
+        match *event {
+            Event::MouseMotion { mousestate, xrel, yrel, .. } if mousestate.right() => {
+                let (right, up) = camera.screen_axes();
+                self.center += (right * xrel as f32 - up * yrel as f32) * 0.01;
+                self.changed = true;
+            }
+            Event::KeyDown { keycode: Some(Keycode::R), repeat: false, .. } => {
+                self.axis = (self.axis + 1) % 3;
+                self.changed = true;
+            }
+            Event::KeyDown { keycode: Some(Keycode::Comma), .. } => {
+                self.half_length *= 0.9;
+                self.changed = true;
+            }
+            Event::KeyDown { keycode: Some(Keycode::Period), .. } => {
+                self.half_length /= 0.9;
+                self.changed = true;
+            }
+            _ => {}
+        }
+    }
+
+    // Returns whether the rake moved since the last tick.
+    pub fn tick(&mut self) -> bool {
+        std::mem::take(&mut self.changed)
+    }
+
+    pub fn draw(&self, graphics: &mut Graphics3D, color: Color) {
+        let mut direction = glm::Vec3::zeros();
+        direction[self.axis] = self.half_length;
+        graphics.line(self.center - direction, self.center + direction, color);
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum FlowStyle {
+    Tubes,
+    Lines,
+    Particles,
+}
+
+impl FlowStyle {
+    pub fn from_name(name: &str) -> Result<FlowStyle, String> {
+        match name {
+            "tube" => Ok(FlowStyle::Tubes),
+            "lines" => Ok(FlowStyle::Lines),
+            "particles" => Ok(FlowStyle::Particles),
+            _ => Err(format!("unknown style '{}', expected tube, lines or particles", name)),
+        }
+    }
+}
+
+struct Particle {
+    trail: VecDeque<[f64; 3]>,
+    age: usize,
+    seed: usize,
+}
+
+const PARTICLES_PER_SEED: usize = 24;
+const LIFETIME: usize = 240;
+const TRAIL: usize = 12;
+
+// Streamlines from a seed rake through the vector field F(x, y, z) in the cube [min, max]^3, or particles
+// carried along by it, colored by the speed |F|.
+pub struct Flow {
+    field: VectorField,
+    min: f64,
+    max: f64,
+    // Speeds at or above this get the top color.
+    reference_speed: f64,
+    style: FlowStyle,
+    rake: SeedRake,
+    particles: Vec<Particle>,
+}
+
+impl Flow {
+    pub fn new(exprs: [&Expr; 3], min: f32, max: f32, style: FlowStyle, seeds: usize) -> Result<Self, String> {
+        let field = VectorField::new(exprs)?;
+        let (min, max) = (min as f64, max as f64);
+
+        let step = (max - min) / 10.0;
+        let [xs, ys, zs] = SampleGrid::points([min; 3], [step; 3], [10; 3]);
+        let mut speeds: Vec<f64> = (0..xs.len()).map(|k| field.speed([xs[k], ys[k], zs[k]])).filter(|s| s.is_finite() && *s > 0.0).collect();
+        if speeds.is_empty() {
+            return Err("the field is zero or undefined everywhere in the plotted region".to_string());
+        }
+        speeds.sort_unstable_by(f64::total_cmp);
+        let reference_speed = speeds[(speeds.len() - 1) * 95 / 100];
+
+        let rake = SeedRake::new(glm::Vec3::zeros(), 0.4 * (max - min) as f32, seeds);
+        Ok(Flow { field, min, max, reference_speed, style, rake, particles: Vec::new() })
+    }
+
+    pub fn process_event(&mut self, event: &Event, camera: &Camera) {
+        self.rake.process_event(event, camera);
+    }
+
+    // Moves the particles along, returning whether the flow has to be redrawn: every frame for
+    // particles, otherwise only when the rake moved.
+    pub fn tick(&mut self) -> bool {
+        let moved = self.rake.tick();
+        if self.style != FlowStyle::Particles {
+            return moved;
+        }
+
+        let seeds = self.rake.seeds();
+        if moved || self.particles.is_empty() {
+            // Each particle starts out as far along as its age, so that they don't all leave the rake
+            // together.
+            let count = seeds.len() * PARTICLES_PER_SEED;
+            self.particles = (0..count).map(|i| Particle { trail: VecDeque::from([seeds[i % seeds.len()]]), age: 0, seed: i % seeds.len() }).collect();
+            for i in 0..count {
+                for _ in 0..i * LIFETIME / count {
+                    self.advance(i, &seeds);
+                }
+            }
+        }
+
+        for i in 0..self.particles.len() {
+            self.advance(i, &seeds);
+        }
+        true
+    }
+
+    // Moves a particle one frame along, sending it back to its seed once it is too old or leaves the cube.
+    fn advance(&mut self, i: usize, seeds: &[[f64; 3]]) {
+        // About six seconds at 60 frames a second to cross the cube at the reference speed.
+        let dt = (self.max - self.min) / (self.reference_speed * 360.0);
+        let inside = |p: &[f64; 3]| p.iter().all(|c| c.is_finite() && (self.min..=self.max).contains(c));
+
+        let point = self.field.advect(*self.particles[i].trail.back().unwrap(), dt);
+        let particle = &mut self.particles[i];
+        particle.age += 1;
+        if particle.age >= LIFETIME || !inside(&point) {
+            particle.trail = VecDeque::from([seeds[particle.seed]]);
+            particle.age = 0;
+            return;
+        }
+        particle.trail.push_back(point);
+        if particle.trail.len() > TRAIL {
+            particle.trail.pop_front();
+        }
+    }
+
+    pub fn draw(&self, graphics: &mut Graphics3D) {
+        self.rake.draw(graphics, Color::from_rgb(0.9, 0.9, 0.9));
+
+        let curves: Vec<Curve> = match self.style {
+            FlowStyle::Particles => self.particles.iter().filter(|particle| particle.trail.len() > 1).map(|particle| self.curve(particle.trail.iter().copied())).collect(),
+            _ => {
+                let step_length = (self.max - self.min) / 200.0;
+                self.rake.seeds().into_iter().map(|seed| self.curve(self.field.streamline(seed, step_length, 800, self.min, self.max))).collect()
+            }
+        };
+
+        let radius = 0.006 * (self.max - self.min) as f32;
+        for curve in curves {
+            let colors = curve.colors_between(0.0, self.reference_speed);
+            match self.style {
+                FlowStyle::Tubes => curve.tube(graphics, &colors, radius, 8, Frames::RotationMinimizing),
+                FlowStyle::Lines | FlowStyle::Particles => curve.lines(graphics, &colors),
+            }
+        }
+    }
+
+    fn curve(&self, points: impl IntoIterator<Item = [f64; 3]>) -> Curve {
+        let points: Vec<[f64; 3]> = points.into_iter().collect();
+        Curve {
+            values: points.iter().map(|&p| self.field.speed(p)).collect(),
+            points: points.iter().map(|p| glm::Vec3::new(p[0] as f32, p[1] as f32, p[2] as f32)).collect(),
+        }
+    }
+}
@@ -0,0 +1,106 @@
+use std::ffi::CString;
+
+use crate::graphics::color::*;
+use crate::graphics::objects::*;
+use crate::math::{expr::Expr, glsl::GLSL_PRELUDE};
+
+use super::camera::Camera;
+
+// A height field y = f(x, z, t) evaluated in the vertex shader over a static (x, z) grid.
+pub struct GpuSurface {
+    program: Program,
+    vbo: Vbo,
+    vao: Vao,
+    ibo: Ibo,
+    indices: u32,
+    color: Color,
+    u_world_to_screen: Uniform,
+    u_lighting: Uniform,
+    u_color: Uniform,
+    u_time: Option<Uniform>,
+}
+
+impl GpuSurface {
+    pub fn new(expr: &Expr, x_min: f32, x_max: f32, z_min: f32, z_max: f32, steps: u32) -> Result<Self, String> {
+        let variables = [("x", "x"), ("z", "z"), ("t", "t")];
+        let function = |name: &str, expr: &Expr| -> Result<String, String> {
+            Ok(format!("float {}(float x, float z, float t) {{\n    return {};\n}}\n", name, expr.to_glsl(&variables)?))
+        };
+        let functions = [
+            GLSL_PRELUDE.to_string(),
+            function("surface_y", expr)?,
+            function("surface_dx", &expr.derivative("x").simplify())?,
+            function("surface_dz", &expr.derivative("z").simplify())?,
+        ];
+        let source = include_str!("./graphics3dsurface.vert").replace("// SURFACE FUNCTIONS", &functions.join("\n"));
+
+        let vert_shader = Shader::from_source(&CString::new(source).unwrap(), gl::VERTEX_SHADER)?;
+        let frag_shader = Shader::from_source(&CString::new(include_str!("./graphics3d.frag")).unwrap(), gl::FRAGMENT_SHADER)?;
+
+        let program = Program::from_shaders(&[&vert_shader, &frag_shader])?;
+        program.set();
+
+        let mut vertices = Vec::with_capacity(((steps + 1) * (steps + 1) * 2) as usize);
+        for i in 0..=steps {
+            for j in 0..=steps {
+                vertices.push(x_min + (x_max - x_min) * i as f32 / steps as f32);
+                vertices.push(z_min + (z_max - z_min) * j as f32 / steps as f32);
+            }
+        }
+
+        let mut indices = Vec::with_capacity((steps * steps * 6) as usize);
+        let index = |i: u32, j: u32| i * (steps + 1) + j;
+        for i in 0..steps {
+            for j in 0..steps {
+                indices.extend([index(i, j), index(i, j + 1), index(i + 1, j + 1)]);
+                indices.extend([index(i + 1, j + 1), index(i + 1, j), index(i, j)]);
+            }
+        }
+
+        let vbo = Vbo::new();
+        vbo.bind();
+        let vao = Vao::new(&[VertexArrayElement::Floats { count: 2, normalized: false }]);
+        vao.bind();
+        let ibo = Ibo::new();
+        vbo.set(&vertices);
+        ibo.set(&indices);
+
+        let u_world_to_screen = Uniform::new(&program, "u_world_to_screen")?;
+        let u_lighting = Uniform::new(&program, "u_lighting")?;
+        let u_color = Uniform::new(&program, "u_color")?;
+        // The time uniform is optimized away when the expression doesn't depend on t.
+        let u_time = Uniform::new(&program, "u_time").ok();
+
+        Ok(GpuSurface {
+            program,
+            vbo,
+            vao,
+            ibo,
+            indices: indices.len() as u32,
+            color: Color::from_rgb(0.0, 0.4, 0.6),
+            u_world_to_screen,
+            u_lighting,
+            u_color,
+            u_time,
+        })
+    }
+
+    pub fn render(&self, camera: &Camera, time: f32) {
+        self.program.set();
+
+        self.u_world_to_screen.set_mat4(camera.matrix());
+        self.u_lighting.set_vec3(glm::Vec3::new(0.0, -1.0, 1.0));
+        self.u_color.set_rgb(self.color);
+        if let Some(u_time) = &self.u_time {
+            u_time.set1(time);
+        }
+
+        self.vao.bind();
+        self.vbo.bind();
+        self.ibo.bind();
+
+        unsafe {
+            gl::DrawElements(gl::TRIANGLES, self.indices as gl::types::GLsizei, gl::UNSIGNED_INT, std::ptr::null());
+        }
+    }
+}
@@ -0,0 +1,47 @@
+use crate::math::{expr::Expr, nd::Vector};
+
+use super::{
+    color::Color,
+    graphics3d::{ComplexGrid, Graphics3D, Rect},
+    ndcamera::NdCamera,
+};
+
+// The graph of w = f(z) is a surface in R^4. Points are stored as (Re z, Re w, Im z, Im w), so that
+// before any rotation x and z span the domain, y is Re w and Im w is the hidden fourth coordinate.
+pub struct ComplexGraph4D {
+    points: Vec<[f64; 4]>,
+    count: usize,
+}
+
+impl ComplexGraph4D {
+    // Points where |f(z)| exceeds `cap` are left out, so that poles don't stretch the mesh off to infinity.
+    pub fn new(expr: &Expr, rect: Rect, cap: f32) -> Result<Self, String> {
+        let grid = ComplexGrid::sample(expr, rect)?;
+        let points = grid
+            .z
+            .iter()
+            .zip(&grid.w)
+            .map(|(z, w)| {
+                if w.abs() <= cap as f64 {
+                    [z.a, w.a, z.b, w.b]
+                } else {
+                    [f64::NAN; 4]
+                }
+            })
+            .collect();
+
+        Ok(ComplexGraph4D { points, count: grid.count })
+    }
+
+    // Projects the graph into 3D through `camera` and adds it to `graphics`, colored by the hidden coordinate.
+    pub fn mesh(&self, camera: &NdCamera, graphics: &mut Graphics3D) {
+        let views: Vec<Vector> = self.points.iter().map(|p| camera.to_view(p)).collect();
+        let (lo, hi) = views.iter().filter(|v| v[3].is_finite()).fold((f64::INFINITY, f64::NEG_INFINITY), |(lo, hi), v| (lo.min(v[3]), hi.max(v[3])));
+
+        let positions: Vec<glm::Vec3> = views.iter().map(|v| camera.project_view(v).unwrap_or(glm::Vec3::repeat(f32::NAN))).collect();
+        graphics.grid_mesh(self.count, &positions, |k| {
+            let t = if hi > lo { ((views[k][3] - lo) / (hi - lo)) as f32 } else { 0.5 };
+            Color::from_colormap(t)
+        });
+    }
+}
@@ -1,13 +1,129 @@
-use std::ffi::CString;
+use std::{ffi::CString, time::Instant};
 
 use crate::graphics::color::*;
 use crate::graphics::objects::*;
 
-use super::camera::Camera;
+use crate::math::{
+    bytecode::Bytecode,
+    complex::Complex,
+    expr::{Env, Expr},
+    interval::Interval,
+    isosurface::{Mesh, SampleGrid},
+    nd::Vector,
+    polytope::Polytope,
+};
+
+use super::{
+    arrows::{Arrow, ArrowGlyphs},
+    camera::Camera,
+    gpusurface::GpuSurface,
+    ndcamera::NdCamera,
+};
+
+struct SurfaceGrid {
+    points: Vec<glm::Vec3>,
+    x_count: usize,
+    z_count: usize,
+}
+
+impl SurfaceGrid {
+    fn sample(x_min: f32, x_max: f32, x_step: f32, z_min: f32, z_max: f32, z_step: f32, f: impl Fn(&[f64], &[f64], &mut [f64])) -> Self {
+        let x_count = ((x_max - x_min) / x_step).ceil() as usize;
+        let z_count = ((z_max - z_min) / z_step).ceil() as usize;
+
+        let samples = (x_count + 1) * (z_count + 1);
+        let mut xs = Vec::with_capacity(samples);
+        let mut zs = Vec::with_capacity(samples);
+        for i in 0..=x_count {
+            for j in 0..=z_count {
+                xs.push((x_min + i as f32 * x_step) as f64);
+                zs.push((z_min + j as f32 * z_step) as f64);
+            }
+        }
+        let mut ys = vec![0.0; samples];
+        f(&xs, &zs, &mut ys);
+
+        let points = (0..samples).map(|k| glm::Vec3::new(xs[k] as f32, ys[k] as f32, zs[k] as f32)).collect();
+        SurfaceGrid { points, x_count, z_count }
+    }
+
+    fn point(&self, i: usize, j: usize) -> glm::Vec3 {
+        self.points[i * (self.z_count + 1) + j]
+    }
+}
+
+// A rectangle [u.0, u.1] x [v.0, v.1] of a plane, sampled with steps + 1 points along each side.
+#[derive(Clone, Copy, Debug)]
+pub struct Rect {
+    pub u: (f32, f32),
+    pub v: (f32, f32),
+    pub steps: u32,
+}
+
+impl Rect {
+    pub fn square(min: f32, max: f32, steps: u32) -> Rect {
+        Rect { u: (min, max), v: (min, max), steps }
+    }
+}
+
+// The cube [min, max]^3, sampled with steps + 1 points along each edge.
+#[derive(Clone, Copy, Debug)]
+pub struct Cube {
+    pub min: f64,
+    pub max: f64,
+    pub steps: usize,
+}
+
+// Samples w = f(z) on a (count + 1) x (count + 1) grid over a rectangle of the complex plane, with u as
+// the real part and v as the imaginary part.
+pub struct ComplexGrid {
+    pub z: Vec<Complex>,
+    pub w: Vec<Complex>,
+    pub count: usize,
+}
+
+impl ComplexGrid {
+    pub fn sample(expr: &Expr, rect: Rect) -> Result<Self, String> {
+        if let Some(name) = expr.variables().into_iter().find(|name| name != "z") {
+            return Err(format!("unknown variable '{}', expected z", name));
+        }
+
+        let count = rect.steps as usize;
+        let ((re_min, re_max), (im_min, im_max)) = (rect.u, rect.v);
+        let (re_step, im_step) = ((re_max - re_min) / rect.steps as f32, (im_max - im_min) / rect.steps as f32);
+        let mut z = Vec::with_capacity((count + 1) * (count + 1));
+        for i in 0..=count {
+            for j in 0..=count {
+                z.push(Complex::new((re_min + i as f32 * re_step) as f64, (im_min + j as f32 * im_step) as f64));
+            }
+        }
+        let w = z.iter().map(|&z| expr.eval_complex(&Env::new().with("z", z))).collect();
+        Ok(ComplexGrid { z, w, count })
+    }
+}
+
+// Position, color and normal.
+const VERTEX_FLOATS: usize = 10;
+
+// Separates line strips in `strip_buffer`.
+const RESTART_INDEX: u32 = u32::MAX;
+
+// The sizes of the buffers at some point, to throw away everything added after it.
+#[derive(Clone, Copy)]
+pub struct BufferMark {
+    vertices: u32,
+    indices: usize,
+    lines: usize,
+    strips: usize,
+    translucent: usize,
+}
 
 pub struct Graphics3D {
     pub vertex_buffer: Vec<f32>,
     pub index_buffer: Vec<u32>,
+    pub line_buffer: Vec<u32>,
+    pub strip_buffer: Vec<u32>,
+    pub translucent_buffer: Vec<u32>,
     pub vertices: u32,
 
     vert_shader: Shader,
@@ -18,6 +134,10 @@ pub struct Graphics3D {
     ibo: Ibo,
     u_world_to_screen: Uniform,
     u_lighting: Uniform,
+
+    gpu_surface: Option<GpuSurface>,
+    arrows: Option<ArrowGlyphs>,
+    start: Instant,
 }
 
 impl Graphics3D {
@@ -32,7 +152,7 @@ impl Graphics3D {
         vbo.bind();
         let vao = Vao::new(&[
             VertexArrayElement::Floats { count: 3, normalized: false },
-            VertexArrayElement::Floats { count: 3, normalized: false },
+            VertexArrayElement::Floats { count: 4, normalized: false },
             VertexArrayElement::Floats { count: 3, normalized: false },
         ]);
         vao.bind();
@@ -45,6 +165,9 @@ impl Graphics3D {
         Ok(Graphics3D {
             vertex_buffer: Vec::new(),
             index_buffer: Vec::new(),
+            line_buffer: Vec::new(),
+            strip_buffer: Vec::new(),
+            translucent_buffer: Vec::new(),
             vertices: 0,
 
             vert_shader,
@@ -55,15 +178,42 @@ impl Graphics3D {
             ibo,
             u_world_to_screen,
             u_lighting,
+
+            gpu_surface: None,
+            arrows: None,
+            start: Instant::now(),
         })
     }
 
     pub fn clear(&mut self) {
         self.vertex_buffer.clear();
         self.index_buffer.clear();
+        self.line_buffer.clear();
+        self.strip_buffer.clear();
+        self.translucent_buffer.clear();
         self.vertices = 0;
     }
 
+    pub fn mark(&self) -> BufferMark {
+        BufferMark {
+            vertices: self.vertices,
+            indices: self.index_buffer.len(),
+            lines: self.line_buffer.len(),
+            strips: self.strip_buffer.len(),
+            translucent: self.translucent_buffer.len(),
+        }
+    }
+
+    // Removes everything added since `mark` was taken, so that part of a scene can be rebuilt.
+    pub fn truncate(&mut self, mark: BufferMark) {
+        self.vertices = mark.vertices;
+        self.vertex_buffer.truncate(mark.vertices as usize * VERTEX_FLOATS);
+        self.index_buffer.truncate(mark.indices);
+        self.line_buffer.truncate(mark.lines);
+        self.strip_buffer.truncate(mark.strips);
+        self.translucent_buffer.truncate(mark.translucent);
+    }
+
     pub fn vertex(&mut self, position: glm::Vec3, color: Color, normal: glm::Vec3) -> u32 {
         let id = self.vertices;
         self.vertices += 1;
@@ -73,6 +223,7 @@ impl Graphics3D {
         self.vertex_buffer.push(color.r);
         self.vertex_buffer.push(color.g);
         self.vertex_buffer.push(color.b);
+        self.vertex_buffer.push(color.a);
         self.vertex_buffer.push(normal.x);
         self.vertex_buffer.push(normal.y);
         self.vertex_buffer.push(normal.z);
@@ -105,46 +256,486 @@ impl Graphics3D {
         self.index_buffer.push(i0);
     }
 
-    pub fn surface(&mut self, x_min: f32, x_max: f32, x_step: f32, z_min: f32, z_max: f32, z_step: f32, f: fn(f32, f32) -> f32) {
+    // Lines and translucent triangles are unlit, their vertices get a zero normal.
+    pub fn line(&mut self, p0: glm::Vec3, p1: glm::Vec3, color: Color) {
+        let i0 = self.vertex(p0, color, glm::Vec3::zeros());
+        let i1 = self.vertex(p1, color, glm::Vec3::zeros());
+
+        self.line_buffer.push(i0);
+        self.line_buffer.push(i1);
+    }
+
+    // A polyline through `points`, broken wherever a point isn't finite.
+    pub fn line_strip(&mut self, points: &[glm::Vec3], colors: &[Color]) {
+        for (&point, &color) in points.iter().zip(colors) {
+            if point.iter().all(|c| c.is_finite()) {
+                let id = self.vertex(point, color, glm::Vec3::zeros());
+                self.strip_buffer.push(id);
+            } else if self.strip_buffer.last().is_some_and(|&id| id != RESTART_INDEX) {
+                self.strip_buffer.push(RESTART_INDEX);
+            }
+        }
+        if self.strip_buffer.last().is_some_and(|&id| id != RESTART_INDEX) {
+            self.strip_buffer.push(RESTART_INDEX);
+        }
+    }
+
+    // Drawn after everything opaque, blended by the alpha of `color`.
+    pub fn translucent_triangle(&mut self, p0: glm::Vec3, p1: glm::Vec3, p2: glm::Vec3, color: Color) {
+        let i0 = self.vertex(p0, color, glm::Vec3::zeros());
+        let i1 = self.vertex(p1, color, glm::Vec3::zeros());
+        let i2 = self.vertex(p2, color, glm::Vec3::zeros());
+
+        self.translucent_buffer.push(i0);
+        self.translucent_buffer.push(i1);
+        self.translucent_buffer.push(i2);
+    }
+
+    // Samples the whole grid in one call, with `f(xs, zs, ys)` filling in the heights.
+    pub fn surface(&mut self, x_min: f32, x_max: f32, x_step: f32, z_min: f32, z_max: f32, z_step: f32, f: impl Fn(&[f64], &[f64], &mut [f64])) {
+        let grid = SurfaceGrid::sample(x_min, x_max, x_step, z_min, z_max, z_step, f);
+        self.surface_mesh(&grid, |_, _| true);
+    }
+
+    // Plots y = expr(x, z). Cells where interval evaluation finds a pole, a jump or a domain boundary
+    // are left out, so that functions like 1/x or tan(x) don't get walls across their discontinuities.
+    pub fn surface_expr(&mut self, expr: &Expr, x_min: f32, x_max: f32, z_min: f32, z_max: f32, steps: u32) -> Result<(), String> {
+        let f = expr.compile(&["x", "z"])?;
+        let (x_step, z_step) = ((x_max - x_min) / steps as f32, (z_max - z_min) / steps as f32);
+        let grid = SurfaceGrid::sample(x_min, x_max, x_step, z_min, z_max, z_step, |xs, zs, ys| f.eval_slice(&[xs, zs], ys));
+
+        self.surface_mesh(&grid, |i, j| {
+            let (p00, p11) = (grid.point(i, j), grid.point(i + 1, j + 1));
+            let env = Env::new().with("x", Interval::new(p00.x as f64, p11.x as f64)).with("z", Interval::new(p00.z as f64, p11.z as f64));
+            let bounds = expr.eval_interval(&env);
+            !bounds.discontinuous && !bounds.partial
+        });
+        Ok(())
+    }
+
+    fn surface_mesh(&mut self, grid: &SurfaceGrid, keep: impl Fn(usize, usize) -> bool) {
         let color = Color::from_rgb(0.4, 0.0, 0.6);
 
-        let mut x = x_min;
-        while x < x_max {
-            let mut z = z_min;
-            while z < z_max {
-                let p00 = glm::Vec3::new(x, f(x, z), z);
-                let p01 = glm::Vec3::new(x, f(x, z + z_step), z + z_step);
-                let p10 = glm::Vec3::new(x + x_step, f(x + x_step, z), z);
-                let p11 = glm::Vec3::new(x + x_step, f(x + x_step, z + z_step), z + z_step);
+        for i in 0..grid.x_count {
+            for j in 0..grid.z_count {
+                let p00 = grid.point(i, j);
+                let p01 = grid.point(i, j + 1);
+                let p10 = grid.point(i + 1, j);
+                let p11 = grid.point(i + 1, j + 1);
+
+                let finite = [p00, p01, p10, p11].iter().all(|p| p.y.is_finite());
+                if finite && keep(i, j) {
+                    self.triangle(p00, p01, p11, color);
+                    self.triangle(p11, p10, p00, color);
+                }
+            }
+        }
+    }
+
+    // Domain coloring of w = f(z) on the plane y = 0, with x and z as the real and imaginary parts of z.
+    pub fn domain_coloring(&mut self, expr: &Expr, rect: Rect) -> Result<(), String> {
+        let grid = ComplexGrid::sample(expr, rect)?;
+        let positions: Vec<glm::Vec3> = grid.z.iter().map(|z| glm::Vec3::new(z.a as f32, 0.0, z.b as f32)).collect();
+        self.grid_mesh(grid.count, &positions, |k| Color::from_complex(grid.w[k]));
+        Ok(())
+    }
+
+    // Plots y = |f(z)| over the complex plane, colored by arg(f(z)). Heights are clamped to `height_cap`
+    // so that poles become plateaus instead of spikes.
+    pub fn complex_surface(&mut self, expr: &Expr, rect: Rect, height_cap: f32) -> Result<(), String> {
+        let grid = ComplexGrid::sample(expr, rect)?;
+        let positions: Vec<glm::Vec3> = grid
+            .z
+            .iter()
+            .zip(&grid.w)
+            .map(|(z, w)| {
+                // Written as a comparison rather than min, which would turn NaN into the cap.
+                let height = w.abs() as f32;
+                glm::Vec3::new(z.a as f32, if height > height_cap { height_cap } else { height }, z.b as f32)
+            })
+            .collect();
+        self.grid_mesh(grid.count, &positions, |k| Color::from_arg(grid.w[k]));
+        Ok(())
+    }
+
+    // Adds a smooth mesh over a (count + 1) x (count + 1) grid of shared vertices, stored row by row.
+    // Normals come from the neighbouring points, and cells with a non-finite corner are left out.
+    pub fn grid_mesh(&mut self, count: usize, positions: &[glm::Vec3], color: impl Fn(usize) -> Color) {
+        let index = |i: usize, j: usize| i * (count + 1) + j;
+        let finite = |k: usize| positions[k].iter().all(|c| c.is_finite());
+
+        let mut ids = Vec::with_capacity(positions.len());
+        for i in 0..=count {
+            for j in 0..=count {
+                if !finite(index(i, j)) {
+                    ids.push(None);
+                    continue;
+                }
+
+                // Central differences, one sided along the edges of the grid.
+                let (i0, i1) = (i.saturating_sub(1), (i + 1).min(count));
+                let (j0, j1) = (j.saturating_sub(1), (j + 1).min(count));
+                let di = positions[index(i1, j)] - positions[index(i0, j)];
+                let dj = positions[index(i, j1)] - positions[index(i, j0)];
+                let normal = dj.cross(&di);
+                let normal = if normal.iter().all(|c| c.is_finite()) && normal != glm::Vec3::zeros() { glm::normalize(&normal) } else { glm::Vec3::y() };
+
+                ids.push(Some(self.vertex(positions[index(i, j)], color(index(i, j)), normal)));
+            }
+        }
+
+        for i in 0..count {
+            for j in 0..count {
+                if let (Some(i00), Some(i01), Some(i10), Some(i11)) = (ids[index(i, j)], ids[index(i, j + 1)], ids[index(i + 1, j)], ids[index(i + 1, j + 1)]) {
+                    self.index_buffer.extend([i00, i01, i11, i11, i10, i00]);
+                }
+            }
+        }
+    }
+
+    // Draws the edges of an N-D polytope as lines and its 2-faces as translucent polygons, after
+    // projecting it to 3D through `camera`.
+    pub fn polytope(&mut self, polytope: &Polytope, camera: &NdCamera, edge_color: Color, face_color: Color) {
+        let positions: Vec<Option<glm::Vec3>> = polytope.vertices.iter().map(|v| camera.project(v.as_slice())).collect();
+
+        for &(a, b) in &polytope.edges {
+            if let (Some(p0), Some(p1)) = (positions[a], positions[b]) {
+                self.line(p0, p1, edge_color);
+            }
+        }
+
+        if face_color.a == 0.0 {
+            return;
+        }
+        for face in &polytope.faces {
+            let Some(points) = face.iter().map(|&i| positions[i]).collect::<Option<Vec<_>>>() else {
+                continue;
+            };
+            // Faces are convex, and stay convex under projection.
+            for k in 1..points.len() - 1 {
+                self.translucent_triangle(points[0], points[k], points[k + 1], face_color);
+            }
+        }
+    }
+
+    // Cuts a 4D polytope with the hyperplane where the fourth view coordinate of `camera` equals `w`.
+    // Every cell that crosses it leaves a convex polygon, which is filled and outlined.
+    pub fn polytope_slice(&mut self, polytope: &Polytope, cells: &[Vec<usize>], camera: &NdCamera, w: f64, color: Color, edge_color: Color) {
+        let views: Vec<Vector> = polytope.vertices.iter().map(|v| camera.to_view(v.as_slice())).collect();
+        let crossings: Vec<(usize, usize, glm::Vec3)> = polytope
+            .edges
+            .iter()
+            .filter_map(|&(a, b)| {
+                let (da, db) = (views[a][3] - w, views[b][3] - w);
+                if (da < 0.0) == (db < 0.0) {
+                    return None;
+                }
+                let t = da / (da - db);
+                let point = |v: &Vector| glm::Vec3::new(v[0] as f32, v[1] as f32, v[2] as f32);
+                Some((a, b, point(&views[a]) + (point(&views[b]) - point(&views[a])) * t as f32))
+            })
+            .collect();
+
+        let mut polygons = Vec::new();
+        for cell in cells {
+            let mut points: Vec<glm::Vec3> = Vec::new();
+            for &(a, b, point) in &crossings {
+                if cell.contains(&a) && cell.contains(&b) && !points.iter().any(|p| glm::distance(p, &point) < 1e-6) {
+                    points.push(point);
+                }
+            }
+            if points.len() >= 3 {
+                polygons.push(sort_polygon(points));
+            }
+        }
+
+        let all = polygons.iter().flatten();
+        let center = all.clone().fold(glm::Vec3::zeros(), |sum, p| sum + p) / all.count().max(1) as f32;
+        for polygon in &polygons {
+            // Wound so that the normals point away from the middle of the cross-section.
+            let centroid = polygon.iter().fold(glm::Vec3::zeros(), |sum, p| sum + p) / polygon.len() as f32;
+            let outward = (polygon[1] - polygon[0]).cross(&(polygon[2] - polygon[0])).dot(&(centroid - center)) >= 0.0;
+            for k in 1..polygon.len() - 1 {
+                if outward {
+                    self.triangle(polygon[0], polygon[k], polygon[k + 1], color);
+                } else {
+                    self.triangle(polygon[0], polygon[k + 1], polygon[k], color);
+                }
+            }
+            for k in 0..polygon.len() {
+                self.line(polygon[k], polygon[(k + 1) % polygon.len()], edge_color);
+            }
+        }
+    }
+
+    // Cuts the implicit hypersurface f(x, y, z, w) = 0 with the hyperplane where the fourth view
+    // coordinate of `camera` equals `w`, sampling `cube` in view space.
+    pub fn implicit_slice(&mut self, f: &Bytecode, camera: &NdCamera, w: f64, cube: Cube, color: Color) {
+        let step = (cube.max - cube.min) / cube.steps as f64;
+        let grid = SampleGrid::sample([cube.min; 3], [step; 3], [cube.steps; 3], |xs, ys, zs, values| {
+            // The view point (x, y, z, w) is x, y and z times the first three view axes, plus w times the
+            // fourth, in world coordinates.
+            let axes: [&[f64]; 4] = std::array::from_fn(|axis| camera.view_axis(axis));
+            let world: [Vec<f64>; 4] = std::array::from_fn(|c| (0..xs.len()).map(|k| xs[k] * axes[0][c] + ys[k] * axes[1][c] + zs[k] * axes[2][c] + w * axes[3][c]).collect());
+            f.eval_slice(&[&world[0], &world[1], &world[2], &world[3]], values);
+        });
+
+        let mesh = grid.marching_tetrahedra(0.0);
+        self.mesh(&mesh, &mesh.vertex_normals(), color);
+    }
+
+    // Plots the implicit surface f(x, y, z) = 0 over the cube [min, max]^3. Normals follow the gradient
+    // of f, except where it vanishes, like at the tip of a cone, where the triangles around the vertex
+    // decide instead.
+    pub fn implicit_surface(&mut self, expr: &Expr, min: f64, max: f64, steps: usize, color: Color) -> Result<(), String> {
+        let inputs = ["x", "y", "z"];
+        let f = expr.compile(&inputs)?;
+        let gradient = inputs.iter().map(|input| expr.derivative(input).simplify().compile(&inputs)).collect::<Result<Vec<_>, _>>()?;
+
+        let step = (max - min) / steps as f64;
+        let grid = SampleGrid::sample([min; 3], [step; 3], [steps; 3], |xs, ys, zs, values| f.eval_slice(&[xs, ys, zs], values));
+        let mesh = grid.marching_tetrahedra(0.0);
+
+        let normals = mesh
+            .vertices
+            .iter()
+            .zip(mesh.vertex_normals())
+            .map(|(point, fallback)| {
+                let normal: [f64; 3] = std::array::from_fn(|axis| gradient[axis].eval(point));
+                let length = normal.iter().map(|x| x * x).sum::<f64>().sqrt();
+                if length.is_finite() && length > 1e-12 { normal.map(|x| x / length) } else { fallback }
+            })
+            .collect::<Vec<_>>();
+        self.mesh(&mesh, &normals, color);
+        Ok(())
+    }
+
+    // Adds an indexed triangle mesh, sharing its vertices between triangles.
+    pub fn mesh(&mut self, mesh: &Mesh, normals: &[[f64; 3]], color: Color) {
+        let vec3 = |p: [f64; 3]| glm::Vec3::new(p[0] as f32, p[1] as f32, p[2] as f32);
+        let first = self.vertices;
+        for (&point, &normal) in mesh.vertices.iter().zip(normals) {
+            self.vertex(vec3(point), color, vec3(normal));
+        }
+        self.index_buffer.extend(mesh.triangles.iter().flatten().map(|&v| first + v as u32));
+    }
+
+    // Plots the surface (x(u, v), y(u, v), z(u, v)) over the grid of `rect`. Normals are the
+    // cross product of the partial derivatives. Where the far edge of the grid lands back on the near
+    // one, as it does on a sphere, or reversed on a Möbius strip, the vertices are shared across the
+    // seam as long as their normals agree.
+    pub fn parametric_surface(&mut self, exprs: [&Expr; 3], rect: Rect, color: Color) -> Result<(), String> {
+        let count = rect.steps as usize;
+        let ((u_min, u_max), (v_min, v_max)) = (rect.u, rect.v);
+        let index = |i: usize, j: usize| i * (count + 1) + j;
+        let (u_step, v_step) = ((u_max - u_min) as f64 / count as f64, (v_max - v_min) as f64 / count as f64);
+        let mut us = Vec::with_capacity((count + 1) * (count + 1));
+        let mut vs = Vec::with_capacity((count + 1) * (count + 1));
+        for i in 0..=count {
+            for j in 0..=count {
+                us.push(u_min as f64 + i as f64 * u_step);
+                vs.push(v_min as f64 + j as f64 * v_step);
+            }
+        }
+
+        // x, dx/du, dx/dv, y, dy/du, ... so the partials of a point can be read off in one go.
+        let mut functions = Vec::with_capacity(9);
+        for expr in exprs {
+            for f in [expr.clone(), expr.derivative("u").simplify(), expr.derivative("v").simplify()] {
+                functions.push(f.compile(&["u", "v"])?);
+            }
+        }
+        let columns: Vec<Vec<f64>> = functions
+            .iter()
+            .map(|f| {
+                let mut column = vec![0.0; us.len()];
+                f.eval_slice(&[&us, &vs], &mut column);
+                column
+            })
+            .collect();
+
+        let position = |k: usize| glm::Vec3::new(columns[0][k] as f32, columns[3][k] as f32, columns[6][k] as f32);
+        let finite = |p: glm::Vec3| p.iter().all(|c| c.is_finite());
+        let normal_of = |d: [f64; 9]| glm::Vec3::new(d[1] as f32, d[4] as f32, d[7] as f32).cross(&glm::Vec3::new(d[2] as f32, d[5] as f32, d[8] as f32));
+        let usable = |n: glm::Vec3| finite(n) && n.norm() > 1e-12;
+
+        let normals: Vec<glm::Vec3> = (0..us.len())
+            .map(|k| {
+                let normal = normal_of(std::array::from_fn(|c| columns[c][k]));
+                if usable(normal) {
+                    return glm::normalize(&normal);
+                }
+                // At a pole, like the ends of a sphere, a partial derivative vanishes. Stepping slightly
+                // into the grid gives the normal in the limit.
+                let (i, j) = (k / (count + 1), k % (count + 1));
+                let nudge = |index: usize, step: f64| if index == count { -0.01 * step } else { 0.01 * step };
+                let (u, v) = (us[k] + nudge(i, u_step), vs[k] + nudge(j, v_step));
+                let normal = normal_of(std::array::from_fn(|c| functions[c].eval(&[u, v])));
+                if usable(normal) { glm::normalize(&normal) } else { glm::Vec3::y() }
+            })
+            .collect();
 
-                self.triangle(p00, p01, p11, color);
-                self.triangle(p11, p10, p00, color);
+        let extent = (0..us.len()).map(position).filter(|&p| finite(p)).fold(1.0f32, |extent, p| extent.max(p.amax()));
+        let mut welded: Vec<Option<usize>> = vec![None; us.len()];
+        for along_u in [true, false] {
+            // The point `b` steps along the edge where the other parameter is at its start (`a` = 0) or end (`a` = count).
+            let edge = |a: usize, b: usize| if along_u { index(a, b) } else { index(b, a) };
+            for reversed in [false, true] {
+                let partner = |b: usize| edge(0, if reversed { count - b } else { b });
+                let pairs = || (0..=count).map(|b| (edge(count, b), partner(b))).filter(|&(k, _)| finite(position(k)));
+                if pairs().next().is_none() || pairs().any(|(k, target)| (position(k) - position(target)).norm() > 1e-5 * extent) {
+                    continue;
+                }
+                for (k, mut target) in pairs() {
+                    // The far corner gets welded along both seams, so targets are followed to the vertex
+                    // that is actually kept.
+                    while let Some(next) = welded[target] {
+                        target = next;
+                    }
+                    if target != k && welded[k].is_none() && normals[k].dot(&normals[target]) > 0.0 {
+                        welded[k] = Some(target);
+                    }
+                }
+                break;
+            }
+        }
+
+        let mut ids: Vec<Option<u32>> = (0..us.len()).map(|k| (welded[k].is_none() && finite(position(k))).then(|| self.vertex(position(k), color, normals[k]))).collect();
+        for k in 0..us.len() {
+            let mut target = k;
+            while let Some(next) = welded[target] {
+                target = next;
+            }
+            ids[k] = ids[target];
+        }
 
-                z += z_step;
+        for i in 0..count {
+            for j in 0..count {
+                if let (Some(i00), Some(i01), Some(i10), Some(i11)) = (ids[index(i, j)], ids[index(i, j + 1)], ids[index(i + 1, j)], ids[index(i + 1, j + 1)]) {
+                    self.index_buffer.extend([i00, i10, i11, i11, i01, i00]);
+                }
             }
-            x += x_step;
         }
+        Ok(())
+    }
+
+    // Replaces the arrows, which are drawn on the GPU with instancing so that there can be many of them.
+    // Arrows without a direction, of zero or non-finite length, are left out, since the shader divides
+    // by the length.
+    pub fn arrows(&mut self, arrows: &[Arrow]) -> Result<(), String> {
+        let arrows: Vec<Arrow> = arrows.iter().filter(|arrow| arrow.position.iter().all(|c| c.is_finite()) && arrow.vector.norm().is_finite() && arrow.vector.norm() > 1e-6).copied().collect();
+        self.arrows = Some(ArrowGlyphs::new(&arrows)?);
+        Ok(())
+    }
+
+    // Plots the vector field F(x, y, z) as arrows at the centers of a steps x steps x steps grid over the
+    // cube [min, max]^3. Arrows are scaled and colored by |F|, relative to a magnitude that most of the
+    // field stays below, so that a singularity doesn't shrink everything else to nothing.
+    pub fn vector_field(&mut self, exprs: [&Expr; 3], min: f32, max: f32, steps: u32) -> Result<(), String> {
+        let spacing = (max - min) as f64 / steps as f64;
+        let start = min as f64 + spacing / 2.0;
+        let [xs, ys, zs] = SampleGrid::points([start; 3], [spacing; 3], [steps as usize - 1; 3]);
+
+        let mut components = [vec![0.0; xs.len()], vec![0.0; xs.len()], vec![0.0; xs.len()]];
+        for (expr, component) in exprs.iter().zip(&mut components) {
+            expr.compile(&["x", "y", "z"])?.eval_slice(&[&xs, &ys, &zs], component);
+        }
+        let [fx, fy, fz] = components;
+        let vectors: Vec<glm::Vec3> = (0..xs.len()).map(|k| glm::Vec3::new(fx[k] as f32, fy[k] as f32, fz[k] as f32)).collect();
+
+        let mut magnitudes: Vec<f32> = vectors.iter().map(|v| v.norm()).filter(|m| m.is_finite() && *m > 0.0).collect();
+        if magnitudes.is_empty() {
+            return Err("the field is zero or undefined everywhere in the plotted region".to_string());
+        }
+        magnitudes.sort_unstable_by(f32::total_cmp);
+        let reference = magnitudes[(magnitudes.len() - 1) * 95 / 100];
+
+        let arrows: Vec<Arrow> = vectors
+            .iter()
+            .enumerate()
+            .filter(|(_, v)| v.norm().is_finite() && v.norm() > 0.0)
+            .map(|(k, v)| {
+                let t = (v.norm() / reference).min(1.0);
+                let vector = v.normalize() * (0.9 * spacing as f32 * t);
+                // Centered on the sample point.
+                let position = glm::Vec3::new(xs[k] as f32, ys[k] as f32, zs[k] as f32) - vector / 2.0;
+                Arrow { position, vector, color: Color::from_colormap(t) }
+            })
+            .collect();
+        self.arrows(&arrows)
+    }
+
+    // Replaces the GPU evaluated surface, a function of x, z and the time t in seconds.
+    pub fn gpu_surface(&mut self, expr: &Expr, x_min: f32, x_max: f32, z_min: f32, z_max: f32, steps: u32) -> Result<(), String> {
+        self.gpu_surface = Some(GpuSurface::new(expr, x_min, x_max, z_min, z_max, steps)?);
+        Ok(())
     }
 
     pub fn render(&mut self, camera: &Camera) {
+        unsafe {
+            gl::ClearColor(0.1, 0.1, 0.1, 1.0);
+            gl::Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT);
+
+            gl::Enable(gl::DEPTH_TEST);
+            gl::DepthFunc(gl::LESS);
+            gl::DepthMask(gl::TRUE);
+        }
+
+        if let Some(gpu_surface) = &self.gpu_surface {
+            gpu_surface.render(camera, self.start.elapsed().as_secs_f32());
+        }
+        if let Some(arrows) = &self.arrows {
+            arrows.render(camera);
+        }
+
         self.program.set();
 
         self.u_world_to_screen.set_mat4(camera.matrix());
         self.u_lighting.set_vec3(glm::Vec3::new(0.0, -1.0, 1.0));
 
+        // All four kinds of primitives share one index buffer, one after the other.
+        let lines_start = self.index_buffer.len();
+        let strips_start = lines_start + self.line_buffer.len();
+        let translucent_start = strips_start + self.strip_buffer.len();
+        let mut indices = Vec::with_capacity(translucent_start + self.translucent_buffer.len());
+        indices.extend(&self.index_buffer);
+        indices.extend(&self.line_buffer);
+        indices.extend(&self.strip_buffer);
+        indices.extend(&self.translucent_buffer);
+
         self.vao.bind();
         self.vbo.set(&self.vertex_buffer);
-        self.ibo.set(&self.index_buffer);
+        self.ibo.set(&indices);
 
+        let offset = |start: usize| (start * std::mem::size_of::<u32>()) as *const gl::types::GLvoid;
         unsafe {
-            gl::ClearColor(0.1, 0.1, 0.1, 1.0);
-            gl::Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT);
+            gl::DrawElements(gl::TRIANGLES, self.index_buffer.len() as gl::types::GLsizei, gl::UNSIGNED_INT, offset(0));
+            gl::DrawElements(gl::LINES, self.line_buffer.len() as gl::types::GLsizei, gl::UNSIGNED_INT, offset(lines_start));
 
-            gl::Enable(gl::DEPTH_TEST);
-            gl::DepthFunc(gl::LESS);
-            gl::DepthMask(gl::TRUE);
+            gl::Enable(gl::PRIMITIVE_RESTART);
+            gl::PrimitiveRestartIndex(RESTART_INDEX);
+            gl::DrawElements(gl::LINE_STRIP, self.strip_buffer.len() as gl::types::GLsizei, gl::UNSIGNED_INT, offset(strips_start));
+            gl::Disable(gl::PRIMITIVE_RESTART);
 
-            gl::DrawElements(gl::TRIANGLES, self.vertices as gl::types::GLsizei, gl::UNSIGNED_INT, 0 as *const _);
+            // Translucent triangles are tested against the depth buffer but don't write to it, so
+            // they don't hide each other.
+            gl::Enable(gl::BLEND);
+            gl::BlendFunc(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA);
+            gl::DepthMask(gl::FALSE);
+            gl::DrawElements(gl::TRIANGLES, self.translucent_buffer.len() as gl::types::GLsizei, gl::UNSIGNED_INT, offset(translucent_start));
+            gl::DepthMask(gl::TRUE);
+            gl::Disable(gl::BLEND);
         }
     }
 }
+
+// Orders the corners of a convex planar polygon by their angle around its centroid.
+fn sort_polygon(mut points: Vec<glm::Vec3>) -> Vec<glm::Vec3> {
+    let centroid = points.iter().fold(glm::Vec3::zeros(), |sum, p| sum + p) / points.len() as f32;
+    let u = glm::normalize(&(points[0] - centroid));
+    let normal = points.iter().map(|p| u.cross(&(p - centroid))).max_by(|a, b| a.norm().total_cmp(&b.norm())).unwrap();
+    let v = glm::normalize(&normal.cross(&u));
+    let angle = |p: &glm::Vec3| (p - centroid).dot(&v).atan2((p - centroid).dot(&u));
+    points.sort_by(|a, b| angle(a).total_cmp(&angle(b)));
+    points
+}
@@ -0,0 +1,29 @@
+#version 330 core
+
+layout (location = 0) in vec3 a_position;
+layout (location = 1) in vec3 a_normal;
+layout (location = 2) in vec3 a_offset;
+layout (location = 3) in vec3 a_vector;
+layout (location = 4) in vec4 a_color;
+
+uniform mat4 u_world_to_screen;
+uniform vec3 u_lighting;
+
+out vec4 f_color;
+
+void main()
+{
+    // The arrow mesh points along +y with unit length. It is turned to point along a_vector and scaled
+    // by its length in every direction, so short arrows are thin as well.
+    float len = length(a_vector);
+    vec3 up = a_vector / len;
+    vec3 side = normalize(cross(up, abs(up.x) < 0.9 ? vec3(1.0, 0.0, 0.0) : vec3(0.0, 1.0, 0.0)));
+    mat3 basis = mat3(side, up, cross(side, up));
+
+    gl_Position = u_world_to_screen * vec4(a_offset + basis * a_position * len, 1.0);
+
+    vec3 normal = basis * a_normal;
+    float lighting = (1 - dot(normal, u_lighting)) * 0.5;
+
+    f_color = vec4(a_color.rgb * lighting, a_color.a);
+}
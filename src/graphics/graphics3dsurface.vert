@@ -0,0 +1,28 @@
+#version 330 core
+
+layout (location = 0) in vec2 a_position;
+
+uniform mat4 u_world_to_screen;
+uniform vec3 u_lighting;
+uniform vec3 u_color;
+uniform float u_time;
+
+out vec4 f_color;
+
+// SURFACE FUNCTIONS
+
+void main()
+{
+    float x = a_position.x;
+    float z = a_position.y;
+    float t = u_time;
+
+    vec3 position = vec3(x, surface_y(x, z, t), z);
+    vec3 normal = normalize(vec3(-surface_dx(x, z, t), 1.0, -surface_dz(x, z, t)));
+
+    gl_Position = u_world_to_screen * vec4(position, 1.0);
+
+    float lighting = (1 - dot(normal, u_lighting)) * 0.5;
+
+    f_color = vec4(u_color * lighting, 1.0);
+}
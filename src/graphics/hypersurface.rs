@@ -0,0 +1,69 @@
+use crate::math::{bytecode::Bytecode, expr::Expr, isosurface::SampleGrid};
+
+use super::{color::Color, graphics3d::Graphics3D};
+
+// The graph of w = f(x, y, z) is a hypersurface in R^4, shown either as nested level sets or as a plane
+// through the domain colored by w. `inputs` names the variables along the x, y and z axes, so "x, z, y"
+// plots f with its y along the depth axis and its z upwards.
+pub struct Hypersurface {
+    f: Bytecode,
+    grid: SampleGrid,
+    range: (f64, f64),
+}
+
+impl Hypersurface {
+    // Samples the cube [min, max]^3 with `steps` cells along each axis.
+    pub fn new(expr: &Expr, inputs: [&str; 3], min: f32, max: f32, steps: u32) -> Result<Self, String> {
+        let f = expr.compile(&inputs)?;
+        let step = (max - min) as f64 / steps as f64;
+        let grid = SampleGrid::sample([min as f64; 3], [step; 3], [steps as usize; 3], |xs, ys, zs, values| f.eval_slice(&[xs, ys, zs], values));
+
+        // The colors span the bulk of the values, so a pole or two doesn't wash everything else out.
+        let mut finite: Vec<f64> = grid.values.iter().copied().filter(|v| v.is_finite()).collect();
+        if finite.is_empty() {
+            return Err("the function isn't defined anywhere in the plotted region".to_string());
+        }
+        finite.sort_unstable_by(f64::total_cmp);
+        let percentile = |p: f64| finite[((finite.len() - 1) as f64 * p).round() as usize];
+        let range = (percentile(0.02), percentile(0.98));
+
+        Ok(Hypersurface { f, grid, range })
+    }
+
+    fn scale(&self, w: f64) -> f32 {
+        let (lo, hi) = self.range;
+        if hi > lo { ((w - lo) / (hi - lo)) as f32 } else { 0.5 }
+    }
+
+    // Adds the surfaces f = c for `levels` values of c spread evenly over the range of f, translucent so
+    // the inner ones show through the outer ones.
+    pub fn isosurfaces(&self, graphics: &mut Graphics3D, levels: usize) {
+        let (lo, hi) = self.range;
+        let vec3 = |p: [f64; 3]| glm::Vec3::new(p[0] as f32, p[1] as f32, p[2] as f32);
+        for level in 0..levels {
+            let c = lo + (level as f64 + 0.5) / levels as f64 * (hi - lo);
+            let mut color = Color::from_colormap(self.scale(c));
+            color.a = 0.3;
+            let mesh = self.grid.marching_tetrahedra(c);
+            for [i0, i1, i2] in mesh.triangles {
+                graphics.translucent_triangle(vec3(mesh.vertices[i0]), vec3(mesh.vertices[i1]), vec3(mesh.vertices[i2]), color);
+            }
+        }
+    }
+
+    // Adds the horizontal plane at height `y` through the sampled cube, colored by f.
+    pub fn slice(&self, graphics: &mut Graphics3D, y: f64) {
+        let [min, _, _] = self.grid.min;
+        let [step, _, _] = self.grid.step;
+        let [count, _, _] = self.grid.counts;
+        let y = y.clamp(min, min + step * count as f64);
+
+        let [xs, _, zs] = SampleGrid::points([min, y, min], [step, 0.0, step], [count, 0, count]);
+        let ys = vec![y; xs.len()];
+        let mut values = vec![0.0; xs.len()];
+        self.f.eval_slice(&[&xs, &ys, &zs], &mut values);
+
+        let positions: Vec<glm::Vec3> = (0..xs.len()).map(|k| glm::Vec3::new(xs[k] as f32, y as f32, zs[k] as f32)).collect();
+        graphics.grid_mesh(count, &positions, |k| if values[k].is_finite() { Color::from_colormap(self.scale(values[k])) } else { Color::from_rgb(0.5, 0.5, 0.5) });
+    }
+}
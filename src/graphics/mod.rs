@@ -1,7 +1,15 @@
 pub mod objects;
+pub mod arrows;
 pub mod winsdl;
 pub mod color;
+pub mod curve;
+pub mod flow;
 pub mod graphics3d;
+pub mod gpusurface;
+pub mod graph4d;
+pub mod hypersurface;
 pub mod graphicstext;
 pub mod camera;
+pub mod ndcamera;
+pub mod slice;
 pub mod fontatlas;
\ No newline at end of file
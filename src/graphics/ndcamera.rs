@@ -0,0 +1,231 @@
+use sdl2::{event::Event, keyboard::Keycode};
+
+use crate::math::nd::{Matrix, Vector};
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum NdProjection {
+    Orthographic,
+    // Each extra axis is divided out in turn, from the last one down to w, with the eye on that axis.
+    Perspective { eye_distance: f64 },
+}
+
+// Takes N-D points down to 3D through an orthonormal rotation and a projection, ahead of
+// `Camera::matrix` which takes them the rest of the way to the screen.
+pub struct NdCamera {
+    dimensions: usize,
+    // Orthonormal, the rows are the view axes expressed in world coordinates.
+    rotation: Matrix,
+    projection: NdProjection,
+    // The matrices that take view coordinates to 3D. One `Matrix::orthographic`, or a
+    // `Matrix::perspective` for each extra axis, applied in turn to homogeneous coordinates.
+    projection_stages: Vec<Matrix>,
+    // The extra axis that x, y and z currently rotate towards.
+    hidden_axis: usize,
+    rotation_buttons_down: u8,
+    changed: bool,
+}
+
+// Each pair of keys turns one of the x, y and z axes towards the hidden axis, in either direction.
+const ROTATIONS: [(usize, f64); 6] = [(0, 1.0), (0, -1.0), (1, 1.0), (1, -1.0), (2, 1.0), (2, -1.0)];
+
+fn rotation_button(keycode: Keycode) -> Option<u8> {
+    match keycode {
+        Keycode::J => Some(0),
+        Keycode::L => Some(1),
+        Keycode::I => Some(2),
+        Keycode::K => Some(3),
+        Keycode::U => Some(4),
+        Keycode::O => Some(5),
+        _ => None,
+    }
+}
+
+fn axis_key(keycode: Keycode) -> Option<usize> {
+    match keycode {
+        Keycode::Num4 => Some(3),
+        Keycode::Num5 => Some(4),
+        Keycode::Num6 => Some(5),
+        Keycode::Num7 => Some(6),
+        Keycode::Num8 => Some(7),
+        Keycode::Num9 => Some(8),
+        _ => None,
+    }
+}
+
+fn projection_stages(dimensions: usize, projection: NdProjection) -> Vec<Matrix> {
+    match projection {
+        NdProjection::Orthographic => vec![Matrix::orthographic(dimensions, 3)],
+        // Dividing out the last axis first scales the ones before it, including the remaining extra axes.
+        NdProjection::Perspective { eye_distance } => (4..=dimensions).rev().map(|n| Matrix::perspective(n, eye_distance)).collect(),
+    }
+}
+
+impl NdCamera {
+    pub fn new(dimensions: usize) -> Self {
+        assert!(dimensions >= 3, "an N-D camera needs at least 3 dimensions");
+        NdCamera {
+            dimensions,
+            rotation: Matrix::identity(dimensions),
+            projection: NdProjection::Orthographic,
+            projection_stages: projection_stages(dimensions, NdProjection::Orthographic),
+            hidden_axis: 3.min(dimensions - 1),
+            rotation_buttons_down: 0,
+            changed: true,
+        }
+    }
+
+    pub fn dimensions(&self) -> usize {
+        self.dimensions
+    }
+
+    pub fn projection(&self) -> NdProjection {
+        self.projection
+    }
+
+    pub fn set_projection(&mut self, projection: NdProjection) {
+        self.projection = projection;
+        self.projection_stages = projection_stages(self.dimensions, projection);
+        self.changed = true;
+    }
+
+    // Rotates the view by `angle` in the plane spanned by the view axes `a` and `b`.
+    pub fn rotate(&mut self, a: usize, b: usize, angle: f64) {
+        self.rotation = &Matrix::givens(self.dimensions, a, b, angle) * &self.rotation;
+        self.changed = true;
+    }
+
+    // The point in view coordinates, where the first three axes are shown and the rest are projected away.
+    pub fn to_view(&self, point: &[f64]) -> Vector {
+        &self.rotation * &Vector::from(point)
+    }
+
+    // The view axis `axis` in world coordinates.
+    pub fn view_axis(&self, axis: usize) -> &[f64] {
+        self.rotation.row(axis)
+    }
+
+    // Projects a point in view coordinates to 3D. Points behind an eye have no projection.
+    pub fn project_view(&self, view: &Vector) -> Option<glm::Vec3> {
+        let point = match self.projection {
+            NdProjection::Orthographic => &self.projection_stages[0] * view,
+            NdProjection::Perspective { .. } => {
+                // Each stage leaves the scale it divides by in the last coordinate, which turns
+                // negative once the point is behind that eye.
+                let mut point = view.homogeneous();
+                for stage in &self.projection_stages {
+                    point = stage * &point;
+                    if point[point.dimensions() - 1] <= 0.0 {
+                        return None;
+                    }
+                }
+                point.dehomogenize()
+            }
+        };
+        Some(glm::Vec3::new(point[0] as f32, point[1] as f32, point[2] as f32))
+    }
+
+    pub fn project(&self, point: &[f64]) -> Option<glm::Vec3> {
+        self.project_view(&self.to_view(point))
+    }
+
+    pub fn process_event(&mut self, event: &Event) {
+        match *event {
+            Event::KeyDown { keycode: Some(Keycode::P), repeat: false, .. } => {
+                self.set_projection(match self.projection {
+                    NdProjection::Orthographic => NdProjection::Perspective { eye_distance: 8.0 },
+                    NdProjection::Perspective { .. } => NdProjection::Orthographic,
+                });
+            }
+            Event::KeyDown { keycode: Some(keycode), .. } => {
+                if let Some(rotation_button) = rotation_button(keycode) {
+                    self.rotation_buttons_down |= 1 << rotation_button;
+                } else if let Some(axis) = axis_key(keycode).filter(|&axis| axis < self.dimensions) {
+                    self.hidden_axis = axis;
+                }
+            }
+            Event::KeyUp { keycode: Some(keycode), .. } => {
+                if let Some(rotation_button) = rotation_button(keycode) {
+                    self.rotation_buttons_down &= !(1 << rotation_button);
+                }
+            }
+            // Dragging with the right button turns x and y towards the hidden axis.
+            Event::MouseMotion { mousestate, xrel, yrel, .. } if mousestate.right() && self.dimensions > 3 => {
+                self.rotate(0, self.hidden_axis, xrel as f64 * 0.01);
+                self.rotate(1, self.hidden_axis, -yrel as f64 * 0.01);
+            }
+            _ => {}
+        }
+    }
+
+    // Applies the rotations of the held keys, returning whether the view changed since the last tick.
+    pub fn tick(&mut self) -> bool {
+        if self.dimensions > 3 {
+            for (i, &(axis, direction)) in ROTATIONS.iter().enumerate() {
+                if ((self.rotation_buttons_down >> i) & 1) == 1 {
+                    self.rotate(axis, self.hidden_axis, direction * 0.02);
+                }
+            }
+        }
+        if self.changed {
+            self.rotation.orthonormalize();
+        }
+        std::mem::take(&mut self.changed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Divides out the extra axes one at a time, from the last one down to w.
+    fn divide_out(view: &[f64], eye_distance: f64) -> Option<[f64; 3]> {
+        let mut scale = 1.0;
+        for &depth in view[3..].iter().rev() {
+            let divisor = eye_distance - depth * scale;
+            if divisor <= 0.0 {
+                return None;
+            }
+            scale *= eye_distance / divisor;
+        }
+        Some([view[0] * scale, view[1] * scale, view[2] * scale])
+    }
+
+    #[test]
+    fn rotations_stay_orthonormal() {
+        let mut camera = NdCamera::new(5);
+        for k in 0..500 {
+            camera.rotate(k % 3, 3 + k % 2, 0.05);
+            camera.tick();
+        }
+        for i in 0..5 {
+            for j in 0..5 {
+                let dot: f64 = camera.view_axis(i).iter().zip(camera.view_axis(j)).map(|(a, b)| a * b).sum();
+                assert!((dot - if i == j { 1.0 } else { 0.0 }).abs() < 1e-12);
+            }
+        }
+    }
+
+    #[test]
+    fn orthographic_projection_drops_the_extra_axes() {
+        let camera = NdCamera::new(5);
+        assert_eq!(camera.project(&[1.0, 2.0, 3.0, 4.0, 5.0]), Some(glm::Vec3::new(1.0, 2.0, 3.0)));
+    }
+
+    #[test]
+    fn perspective_projection_divides_out_each_extra_axis() {
+        let mut camera = NdCamera::new(6);
+        camera.set_projection(NdProjection::Perspective { eye_distance: 8.0 });
+        for view in [[1.0, 2.0, 3.0, 0.0, 0.0, 0.0], [1.0, -2.0, 0.5, 2.0, -3.0, 1.0], [0.3, 0.2, 0.1, 7.5, 1.0, 0.0], [1.0, 1.0, 1.0, 0.0, 0.0, 9.0]] {
+            let projected = camera.project_view(&Vector::from(&view[..]));
+            match divide_out(&view, 8.0) {
+                Some(expected) => {
+                    let projected = projected.expect("the point is in front of every eye");
+                    for axis in 0..3 {
+                        assert!((projected[axis] as f64 - expected[axis]).abs() < 1e-5, "{:?}: {:?} vs {:?}", view, projected, expected);
+                    }
+                }
+                None => assert_eq!(projected, None, "{:?} is behind an eye", view),
+            }
+        }
+    }
+}
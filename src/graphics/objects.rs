@@ -281,24 +281,42 @@ impl Vao {
             gl::BindVertexArray(id);
         }
 
+        Vao::attributes(format, 0, 0);
+
+        Vao { id }
+    }
+
+    // Like `new`, with the attributes in `instance_format` following the per-vertex ones. Those are
+    // read from `instances` and advance once per instance instead of once per vertex.
+    pub fn new_instanced(format: &[VertexArrayElement], instances: &Vbo, instance_format: &[VertexArrayElement]) -> Self {
+        let vao = Vao::new(format);
+
+        instances.bind();
+        Vao::attributes(instance_format, format.len() as GLuint, 1);
+
+        vao
+    }
+
+    // Points the attributes from location `first` on into the bound buffer, interleaved as in `format`.
+    fn attributes(format: &[VertexArrayElement], first: GLuint, divisor: GLuint) {
         let stride = format.iter().map(|e| e.size()).sum::<u32>();
         let mut offset: u32 = 0;
         for (index, element) in format.iter().enumerate() {
+            let index = first + index as GLuint;
             unsafe {
                 gl::VertexAttribPointer(
-                    index as GLuint,
+                    index,
                     element.count() as GLint,
                     element.gl_type() as GLuint,
                     element.normalized() as GLboolean,
                     stride as GLint,
                     offset as *const gl::types::GLvoid
                 );
-                gl::EnableVertexAttribArray(index as GLuint);
+                gl::EnableVertexAttribArray(index);
+                gl::VertexAttribDivisor(index, divisor);
             }
             offset += element.size();
         }
-
-        Vao { id }
     }
 
     pub fn bind(&self) {
@@ -0,0 +1,37 @@
+use sdl2::{event::Event, keyboard::Keycode};
+
+// Position of the hyperplane that 4D objects are cut with, stepped with the [ and ] keys.
+pub struct SlicePosition {
+    w: f64,
+    step: f64,
+    changed: bool,
+}
+
+impl SlicePosition {
+    pub fn new(w: f64, step: f64) -> Self {
+        SlicePosition { w, step, changed: true }
+    }
+
+    pub fn w(&self) -> f64 {
+        self.w
+    }
+
+    pub fn process_event(&mut self, event: &Event) {
+        match *event {
+            Event::KeyDown { keycode: Some(Keycode::LeftBracket), .. } => {
+                self.w -= self.step;
+                self.changed = true;
+            }
+            Event::KeyDown { keycode: Some(Keycode::RightBracket), .. } => {
+                self.w += self.step;
+                self.changed = true;
+            }
+            _ => {}
+        }
+    }
+
+    // Returns whether the slice moved since the last tick.
+    pub fn tick(&mut self) -> bool {
+        std::mem::take(&mut self.changed)
+    }
+}
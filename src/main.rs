@@ -3,23 +3,120 @@ extern crate nalgebra_glm as glm;
 pub mod graphics;
 pub mod math;
 
-use math::expr::Expr;
-use std::f32::consts::TAU;
+use math::{
+    expr::{Env, Expr},
+    ode::{self, OdeSystem},
+    polytope::Polytope,
+};
+use std::{f32::consts::TAU, rc::Rc};
 
-use graphics::{camera::Camera, graphics3d::Graphics3D, winsdl::*};
+use graphics::{
+    camera::Camera,
+    color::Color,
+    curve::{Curve, Frames},
+    flow::{Flow, FlowStyle},
+    graph4d::ComplexGraph4D,
+    graphics3d::{Cube, Graphics3D, Rect},
+    hypersurface::Hypersurface,
+    ndcamera::NdCamera,
+    slice::SlicePosition,
+    winsdl::*,
+};
 use sdl2::event::Event;
 
-fn f(x: f32, z: f32) -> f32 {
-    0.25 * (x * x + z * z)
+type Drawer = Box<dyn Fn(&NdCamera, &mut Graphics3D)>;
+type SliceDrawer = Box<dyn Fn(&NdCamera, f64, &mut Graphics3D)>;
+type VolumeSliceDrawer = Box<dyn Fn(f64, &mut Graphics3D)>;
+
+// A scene in more than three dimensions, redrawn whenever the N-D camera moves. The slice is drawn on
+// top of the rest, and is the only part that gets redrawn when just the slice position moves.
+struct NdScene {
+    camera: NdCamera,
+    draw: Drawer,
+    draw_slice: Option<SliceDrawer>,
+}
+
+fn parse(source: &str) -> Result<Expr, String> {
+    Expr::parse(source).map_err(|e| format!("{}\n{}", e, e.underline(source)))
+}
+
+// Complex plots also accept the imaginary unit i.
+fn parse_complex(source: &str) -> Result<Expr, String> {
+    Expr::parse_complex(source).map_err(|e| format!("{}\n{}", e, e.underline(source)))
+}
+
+// Implicit plots accept an equation lhs = rhs, read as lhs - rhs = 0.
+fn parse_implicit(source: &str) -> Result<Expr, String> {
+    match source.split_once('=') {
+        Some((lhs, rhs)) => Ok(Expr::sub(parse(lhs)?, parse(rhs)?).simplify()),
+        None => Ok(parse(source)?.simplify()),
+    }
+}
+
+// A system of equations like "x' = y; y' = -x". Equations without a prime, like "s = 10", name
+// constants, which the equations after them can use.
+fn parse_system(source: &str) -> Result<OdeSystem, String> {
+    let mut equations = Vec::new();
+    let mut constants: Vec<(String, f64)> = Vec::new();
+    for equation in source.split(';') {
+        let (lhs, rhs) = equation.split_once('=').ok_or_else(|| format!("expected an equation like x' = y, got '{}'", equation.trim()))?;
+        let rhs = parse(rhs)?.simplify();
+        match lhs.trim().strip_suffix('\'') {
+            Some(name) => equations.push((name.trim().to_string(), rhs)),
+            None => {
+                let env = constants.iter().fold(Env::new(), |env, (name, value)| env.with(name, *value));
+                let value = rhs.eval_with(&env);
+                if !value.is_finite() {
+                    return Err(format!("the constant {} = {} has no finite value", lhs.trim(), rhs));
+                }
+                constants.push((lhs.trim().to_string(), value));
+            }
+        }
+    }
+    if equations.is_empty() {
+        return Err("expected at least one equation like x' = y".to_string());
+    }
+    for (name, expr) in &equations {
+        println!("{}' = {}", name, expr);
+    }
+    OdeSystem::new(&equations, &constants)
+}
+
+// The value of the option `--name=value`.
+fn option<'a>(args: &'a [String], name: &str) -> Option<&'a str> {
+    args.iter().find_map(|arg| arg.strip_prefix("--")?.strip_prefix(name)?.strip_prefix('='))
+}
+
+// Reads a range like "0, tau" for the option `--name=min,max`.
+fn parse_range(args: &[String], name: &str, default: (f32, f32)) -> Result<(f32, f32), String> {
+    let Some(range) = option(args, name) else {
+        return Ok(default);
+    };
+    let (min, max) = range.split_once(',').ok_or_else(|| format!("expected a range like --{}=0,tau, got '{}'", name, range))?;
+    Ok((parse(min)?.eval() as f32, parse(max)?.eval() as f32))
 }
 
 fn main() -> Result<(), String> {
     // font_test();
 
-    let expr = Expr::add(Expr::mul(Expr::Constant(6.0), Expr::Constant(8.0)), Expr::Constant(4.0));
-
-    println!("{}", expr);
-    println!("{}", expr.eval());
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    // Options with a value, like "--axes=x,z,y" for the variables shown along the x, y and z axes of
+    // three-input plots, come on top of the mode.
+    let axes = option(&args, "axes").unwrap_or("x,y,z");
+    let mode = args.iter().find(|arg| arg.starts_with("--") && !arg.contains('=')).map(String::as_str);
+    let default = match mode {
+        Some("--domain" | "--modulus" | "--4d") => "(z^2 - 1) / (z^2 + 1)",
+        Some("--polytope") => "tesseract",
+        Some("--slice") => "24-cell",
+        Some("--isosurfaces" | "--volume-slice") => "x^2 + y^2 - z^2",
+        Some("--implicit") => "x^2 + y^2 - z^2 = 1",
+        Some("--parametric") => "(2 + cos(v)) cos(u); sin(v); (2 + cos(v)) sin(u)",
+        Some("--curve") => "sin(t) + 2sin(2t); cos(t) - 2cos(2t); -sin(3t)",
+        Some("--vectors" | "--flow") => "-z; sin(x); x",
+        Some("--ode") => "s = 10; r = 28; b = 8/3; x' = s(y - x); y' = x(r - z) - y; z' = x y - b z",
+        _ => "0.25(x^2 + z^2)",
+    };
+    let source = args.iter().find(|arg| !arg.starts_with("--")).map(String::as_str).unwrap_or(default);
 
     let mut sdl = Winsdl::new(800, 600, "My window")?;
 
@@ -27,19 +124,264 @@ fn main() -> Result<(), String> {
 
     let scale = TAU.sqrt() * 2.0;
     let steps = 250.0;
-    graphics.surface(-scale, scale, scale / steps, -scale, scale, scale / steps, f);
+
+    let mut nd_scene: Option<NdScene> = None;
+    // Plots of three inputs only redraw the plane through their domain as the slice moves.
+    let mut volume_slice: Option<VolumeSliceDrawer> = None;
+    let mut slice_step = 0.05;
+    let mut flow: Option<Flow> = None;
+
+    if mode == Some("--polytope") {
+        let polytope = Polytope::from_name(source)?;
+        if polytope.dimensions() < 3 {
+            return Err(format!("{} is {}-dimensional, only polytopes in 3 or more dimensions can be shown", source, polytope.dimensions()));
+        }
+        println!("{}: {} vertices, {} edges, {} faces", source, polytope.vertices.len(), polytope.edges.len(), polytope.faces.len());
+
+        let (edge_color, face_color) = (Color::from_rgb(0.9, 0.9, 0.9), Color::from_rgba(0.2, 0.5, 0.9, 0.15));
+        nd_scene = Some(NdScene {
+            camera: NdCamera::new(polytope.dimensions()),
+            draw: Box::new(move |camera, graphics| graphics.polytope(&polytope, camera, edge_color, face_color)),
+            draw_slice: None,
+        });
+    } else if mode == Some("--slice") {
+        // Anything that isn't a polytope is read as an implicit hypersurface f(x, y, z, w) = 0.
+        let (draw, draw_slice): (Drawer, SliceDrawer) = match Polytope::from_name(source) {
+            Ok(polytope) if polytope.dimensions() == 4 => {
+                let polytope = Rc::new(polytope);
+                let cells = polytope.cells();
+                println!("{}: {} cells", source, cells.len());
+
+                let outline = Rc::clone(&polytope);
+                let (color, edge_color) = (Color::from_rgb(0.2, 0.5, 0.9), Color::from_rgb(0.9, 0.9, 0.9));
+                (
+                    Box::new(move |camera, graphics| graphics.polytope(&outline, camera, Color::from_rgb(0.35, 0.35, 0.35), Color::from_rgba(0.0, 0.0, 0.0, 0.0))),
+                    Box::new(move |camera, w, graphics| graphics.polytope_slice(&polytope, &cells, camera, w, color, edge_color)),
+                )
+            }
+            Ok(polytope) => return Err(format!("{} is {}-dimensional, only 4D polytopes can be sliced", source, polytope.dimensions())),
+            Err(_) => {
+                let expr = parse_implicit(source)?;
+                println!("{} = 0", expr);
+                let f = expr.compile(&["x", "y", "z", "w"])?;
+                let color = Color::from_rgb(0.4, 0.0, 0.6);
+                (Box::new(|_, _| {}), Box::new(move |camera, w, graphics| graphics.implicit_slice(&f, camera, w, Cube { min: -2.5, max: 2.5, steps: 64 }, color)))
+            }
+        };
+        nd_scene = Some(NdScene { camera: NdCamera::new(4), draw, draw_slice: Some(draw_slice) });
+    } else if let Some(mode @ ("--parametric" | "--curve" | "--vectors" | "--flow")) = mode {
+        let exprs = source.split(';').map(|part| Ok(parse(part)?.simplify())).collect::<Result<Vec<Expr>, String>>()?;
+        let [x, y, z] = exprs.as_slice() else {
+            return Err(format!("expected three expressions separated by ';', got {}", exprs.len()));
+        };
+        if mode == "--vectors" || mode == "--flow" {
+            println!("F = ({}, {}, {})", x, y, z);
+        } else {
+            println!("(x, y, z) = ({}, {}, {})", x, y, z);
+        }
+
+        if mode == "--vectors" {
+            // "--arrows=n" puts n arrows along each axis.
+            let arrows = option(&args, "arrows").map(|n| n.parse::<u32>().map_err(|_| format!("invalid arrow count '{}'", n))).transpose()?.unwrap_or(22);
+            graphics.vector_field([x, y, z], -scale, scale, arrows.max(1))?;
+        } else if mode == "--flow" {
+            // Streamlines from a rake of "--seeds=n" points, as tubes, lines or moving particles.
+            let style = FlowStyle::from_name(option(&args, "style").unwrap_or("tube"))?;
+            let seeds = option(&args, "seeds").map(|n| n.parse::<usize>().map_err(|_| format!("invalid seed count '{}'", n))).transpose()?.unwrap_or(12);
+            flow = Some(Flow::new([x, y, z], -scale, scale, style, seeds)?);
+        } else if mode == "--parametric" {
+            let rect = Rect { u: parse_range(&args, "u", (0.0, TAU))?, v: parse_range(&args, "v", (0.0, TAU))?, steps: steps as u32 };
+            graphics.parametric_surface([x, y, z], rect, Color::from_rgb(0.4, 0.0, 0.6))?;
+        } else {
+            // Drawn as a tube unless "--style=lines" is given, colored by t either way.
+            let (t_min, t_max) = parse_range(&args, "t", (0.0, TAU))?;
+            let curve = Curve::parametric([x, y, z], t_min, t_max, 4 * steps as u32)?;
+            let colors = curve.colors();
+            match option(&args, "style").unwrap_or("tube") {
+                "lines" => curve.lines(&mut graphics, &colors),
+                "tube" => {
+                    let radius = option(&args, "radius").map(|radius| parse(radius).map(|radius| radius.eval() as f32)).transpose()?.unwrap_or(0.1);
+                    let frames = Frames::from_name(option(&args, "frames").unwrap_or("rmf"))?;
+                    curve.tube(&mut graphics, &colors, radius, 16, frames);
+                }
+                style => return Err(format!("unknown style '{}', expected tube or lines", style)),
+            }
+        }
+    } else if mode == Some("--ode") {
+        let system = parse_system(source)?;
+        let (t_min, t_max) = parse_range(&args, "t", (0.0, 40.0))?;
+        if t_max <= t_min {
+            return Err(format!("the time span {}..{} has to go forwards", t_min, t_max));
+        }
+        let (t_min, t_max) = (t_min as f64, t_max as f64);
+        let number = |name: &str, default: f64| option(&args, name).map(|value| parse(value).map(|value| value.eval())).transpose().map(|value| value.unwrap_or(default));
+
+        // Any number of starting points, like "--initial=1,1,1;-1,0,2", one value for each variable.
+        let initial = option(&args, "initial").map(str::to_string).unwrap_or_else(|| vec!["1"; system.variables().len()].join(","));
+        let initial = initial
+            .split(';')
+            .map(|point| {
+                let point = point.split(',').map(|value| parse(value).map(|value| value.eval())).collect::<Result<Vec<f64>, String>>()?;
+                if point.len() != system.variables().len() {
+                    return Err(format!("expected {} initial values, one for each of {}, got {}", system.variables().len(), system.variables().join(", "), point.len()));
+                }
+                Ok(point)
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+
+        // Adaptive steps by default, or fixed RK4 steps of "--step=h". Either way there are at least
+        // 4 * steps points along each trajectory, to keep the curves smooth.
+        let max_step = (t_max - t_min) / (4.0 * steps);
+        let f = |t: f64, y: &[f64]| system.eval(t, y);
+        let trajectories = initial
+            .iter()
+            .map(|y0| match option(&args, "method").unwrap_or("dp") {
+                "dp" => ode::dormand_prince(f, y0, t_min, t_max, number("tolerance", 1e-6)?, max_step),
+                "rk4" => ode::rk4(f, y0, t_min, t_max, number("step", 0.01)?.min(max_step)),
+                method => Err(format!("unknown method '{}', expected dp or rk4", method)),
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+
+        // The first three variables go along the axes, or those in "--axes=x,z,t", where t is the time.
+        let axes: Vec<&str> = match option(&args, "axes") {
+            Some(axes) => axes.split(',').map(str::trim).collect(),
+            None => system.variables().iter().map(String::as_str).chain(["t"; 3]).take(3).collect(),
+        };
+        let axes = axes
+            .iter()
+            .map(|&axis| {
+                if axis == "t" {
+                    Ok(None)
+                } else {
+                    system.variables().iter().position(|name| name == axis).map(Some).ok_or_else(|| format!("'{}' in --axes isn't a variable or t", axis))
+                }
+            })
+            .collect::<Result<Vec<Option<usize>>, String>>()?;
+        let axes: [Option<usize>; 3] = axes.try_into().map_err(|_| "expected three variables in --axes, like x,z,t".to_string())?;
+
+        let mut curves: Vec<Curve> = trajectories
+            .iter()
+            .map(|trajectory| Curve {
+                points: trajectory.times.iter().zip(&trajectory.states).map(|(&t, y)| glm::Vec3::from_fn(|i, _| axes[i].map_or(t, |k| y[k]) as f32)).collect(),
+                values: trajectory.times.clone(),
+            })
+            .collect();
+
+        // Trajectories can be far bigger or smaller than the rest of the plots, so they are centered and
+        // scaled to fit.
+        let (lo, hi) = curves
+            .iter()
+            .flat_map(|curve| &curve.points)
+            .filter(|p| p.iter().all(|c| c.is_finite()))
+            .fold((glm::Vec3::repeat(f32::INFINITY), glm::Vec3::repeat(f32::NEG_INFINITY)), |(lo, hi), p| (lo.inf(p), hi.sup(p)));
+        let (center, extent) = ((lo + hi) / 2.0, (hi - lo).amax() / 2.0);
+        let fit = if extent.is_finite() && extent > 0.0 { scale / extent } else { 1.0 };
+        println!("scaled by {} around ({}, {}, {})", fit, center.x, center.y, center.z);
+        for point in curves.iter_mut().flat_map(|curve| &mut curve.points) {
+            *point = (*point - center) * fit;
+        }
+
+        for curve in &curves {
+            let colors = curve.colors_between(t_min, t_max);
+            match option(&args, "style").unwrap_or("tube") {
+                "lines" => curve.lines(&mut graphics, &colors),
+                "tube" => {
+                    let radius = option(&args, "radius").map(|radius| parse(radius).map(|radius| radius.eval() as f32)).transpose()?.unwrap_or(0.05);
+                    curve.tube(&mut graphics, &colors, radius, 8, Frames::RotationMinimizing);
+                }
+                style => return Err(format!("unknown style '{}', expected tube or lines", style)),
+            }
+        }
+    } else {
+        let expr = match mode {
+            Some("--implicit") => parse_implicit(source)?,
+            Some("--domain" | "--modulus" | "--4d") => parse_complex(source)?.simplify(),
+            _ => parse(source)?.simplify(),
+        };
+
+        match mode {
+            Some("--domain" | "--4d" | "--isosurfaces" | "--volume-slice") => println!("w = {}", expr),
+            Some("--modulus") => println!("y = |{}|", expr),
+            Some("--implicit") => println!("{} = 0", expr),
+            _ => println!("y = {}", expr),
+        }
+
+        match mode {
+            None => graphics.surface_expr(&expr, -scale, scale, -scale, scale, steps as u32)?,
+            Some("--gpu") => graphics.gpu_surface(&expr, -scale, scale, -scale, scale, steps as u32)?,
+            Some("--domain") => graphics.domain_coloring(&expr, Rect::square(-scale, scale, 2 * steps as u32))?,
+            Some("--modulus") => graphics.complex_surface(&expr, Rect::square(-scale, scale, steps as u32), scale)?,
+            Some("--implicit") => graphics.implicit_surface(&expr, -scale as f64, scale as f64, 96, Color::from_rgb(0.4, 0.0, 0.6))?,
+            Some("--4d") => {
+                let graph = ComplexGraph4D::new(&expr, Rect::square(-scale, scale, steps as u32), 2.0 * scale)?;
+                nd_scene = Some(NdScene {
+                    camera: NdCamera::new(4),
+                    draw: Box::new(move |camera, graphics| graph.mesh(camera, graphics)),
+                    draw_slice: None,
+                });
+            }
+            Some(mode @ ("--isosurfaces" | "--volume-slice")) => {
+                let inputs: Vec<&str> = axes.split(',').map(str::trim).collect();
+                let inputs: [&str; 3] = inputs.try_into().map_err(|_| format!("expected three variables in --axes={}, like x,z,y", axes))?;
+                let hypersurface = Hypersurface::new(&expr, inputs, -scale, scale, 64)?;
+                if mode == "--isosurfaces" {
+                    hypersurface.isosurfaces(&mut graphics, 8);
+                } else {
+                    slice_step = scale as f64 / 32.0;
+                    volume_slice = Some(Box::new(move |y, graphics| hypersurface.slice(graphics, y)));
+                }
+            }
+            Some(option) => return Err(format!("unknown option '{}'", option)),
+        }
+    }
 
     let mut camera = Camera::new();
+    let mut slice = SlicePosition::new(0.0, slice_step);
+    // Everything added after this mark is thrown away and drawn again whenever it changes, like slices,
+    // streamlines and particles.
+    let mut redraw_mark = graphics.mark();
 
     'running: loop {
         for event in sdl.event_pump.poll_iter() {
             match event {
                 Event::Quit { .. } => break 'running,
-                e => camera.process_event(&e),
+                e => {
+                    camera.process_event(&e);
+                    slice.process_event(&e);
+                    if let Some(flow) = &mut flow {
+                        flow.process_event(&e, &camera);
+                    }
+                    if let Some(scene) = &mut nd_scene {
+                        scene.camera.process_event(&e);
+                    }
+                }
             }
         }
 
         camera.tick();
+        let slice_moved = slice.tick();
+        if let Some(draw_slice) = volume_slice.as_ref().filter(|_| slice_moved) {
+            graphics.truncate(redraw_mark);
+            draw_slice(slice.w(), &mut graphics);
+        }
+        if let Some(flow) = &mut flow {
+            if flow.tick() {
+                graphics.truncate(redraw_mark);
+                flow.draw(&mut graphics);
+            }
+        }
+        if let Some(scene) = &mut nd_scene {
+            let camera_moved = scene.camera.tick();
+            if camera_moved {
+                graphics.clear();
+                (scene.draw)(&scene.camera, &mut graphics);
+                redraw_mark = graphics.mark();
+            }
+            if let Some(draw_slice) = scene.draw_slice.as_ref().filter(|_| camera_moved || slice_moved) {
+                graphics.truncate(redraw_mark);
+                draw_slice(&scene.camera, slice.w(), &mut graphics);
+            }
+        }
         graphics.render(&camera);
 
         sdl.window.gl_swap_window();
@@ -0,0 +1,195 @@
+use super::{
+    expr::Expr,
+    func::{Func, Kernel},
+};
+
+// Number of samples each register holds. Big enough to amortize dispatch, small enough to stay in cache.
+const CHUNK: usize = 256;
+// Registers that single evaluations keep on the stack. Deeper expressions fall back to the heap.
+const SCALAR_REGISTERS: usize = 16;
+
+// Registers are used like a stack: an operation on register `r` reads `r` and `r + 1` and writes `r`.
+#[derive(Clone, Copy)]
+enum Op {
+    Const(usize, f64),
+    Load(usize, usize),
+    Neg(usize),
+    Add(usize),
+    Sub(usize),
+    Mul(usize),
+    Div(usize),
+    Pow(usize),
+    Unary(usize, fn(f64) -> f64),
+    Binary(usize, fn(f64, f64) -> f64),
+}
+
+#[derive(Clone)]
+pub struct Bytecode {
+    ops: Vec<Op>,
+    inputs: usize,
+    registers: usize,
+}
+
+impl Expr {
+    // Compiles the expression with its variables bound, in order, to `inputs`.
+    pub fn compile(&self, inputs: &[&str]) -> Result<Bytecode, String> {
+        let mut bytecode = Bytecode { ops: Vec::new(), inputs: inputs.len(), registers: 1 };
+        bytecode.emit(self, inputs, 0)?;
+        Ok(bytecode)
+    }
+}
+
+impl Bytecode {
+    fn emit(&mut self, expr: &Expr, inputs: &[&str], register: usize) -> Result<(), String> {
+        self.registers = self.registers.max(register + 1);
+
+        let binary = |bytecode: &mut Bytecode, lhs: &Expr, rhs: &Expr| {
+            bytecode.emit(lhs, inputs, register)?;
+            bytecode.emit(rhs, inputs, register + 1)
+        };
+
+        let op = match expr {
+            &Expr::Constant(value) => Op::Const(register, value),
+            Expr::ImaginaryUnit => return Err("the imaginary unit can only be used in complex plots".to_string()),
+            Expr::Variable(name) => match inputs.iter().position(|input| input == name) {
+                Some(input) => Op::Load(register, input),
+                None => return Err(format!("unknown variable '{}', expected one of {}", name, inputs.join(", "))),
+            },
+            Expr::Neg(operand) => {
+                self.emit(operand, inputs, register)?;
+                Op::Neg(register)
+            }
+            Expr::Add(lhs, rhs) => {
+                binary(self, lhs, rhs)?;
+                Op::Add(register)
+            }
+            Expr::Sub(lhs, rhs) => {
+                binary(self, lhs, rhs)?;
+                Op::Sub(register)
+            }
+            Expr::Mul(lhs, rhs) => {
+                binary(self, lhs, rhs)?;
+                Op::Mul(register)
+            }
+            Expr::Div(lhs, rhs) => {
+                binary(self, lhs, rhs)?;
+                Op::Div(register)
+            }
+            Expr::Pow(lhs, rhs) => {
+                binary(self, lhs, rhs)?;
+                Op::Pow(register)
+            }
+            Expr::Call(func, args) => self.emit_call(*func, args, inputs, register)?,
+        };
+
+        self.ops.push(op);
+        Ok(())
+    }
+
+    fn emit_call(&mut self, func: Func, args: &[Expr], inputs: &[&str], register: usize) -> Result<Op, String> {
+        for (i, arg) in args.iter().enumerate() {
+            self.emit(arg, inputs, register + i)?;
+        }
+        Ok(match func.kernel() {
+            Kernel::Unary(f) => Op::Unary(register, f),
+            Kernel::Binary(f) => Op::Binary(register, f),
+        })
+    }
+
+    pub fn inputs(&self) -> usize {
+        self.inputs
+    }
+
+    // Evaluates the expression once. Unlike eval_slice this doesn't allocate, so it suits hot loops that
+    // need one value at a time, like stepping ODEs or particles.
+    pub fn eval(&self, args: &[f64]) -> f64 {
+        assert_eq!(args.len(), self.inputs, "wrong number of inputs");
+
+        let mut stack = [0.0; SCALAR_REGISTERS];
+        let mut heap = Vec::new();
+        let registers: &mut [f64] = if self.registers <= SCALAR_REGISTERS {
+            &mut stack
+        } else {
+            heap.resize(self.registers, 0.0);
+            &mut heap
+        };
+
+        for op in &self.ops {
+            match *op {
+                Op::Const(dst, value) => registers[dst] = value,
+                Op::Load(dst, input) => registers[dst] = args[input],
+                Op::Neg(dst) => registers[dst] = -registers[dst],
+                Op::Add(dst) => registers[dst] += registers[dst + 1],
+                Op::Sub(dst) => registers[dst] -= registers[dst + 1],
+                Op::Mul(dst) => registers[dst] *= registers[dst + 1],
+                Op::Div(dst) => registers[dst] /= registers[dst + 1],
+                Op::Pow(dst) => registers[dst] = registers[dst].powf(registers[dst + 1]),
+                Op::Unary(dst, f) => registers[dst] = f(registers[dst]),
+                Op::Binary(dst, f) => registers[dst] = f(registers[dst], registers[dst + 1]),
+            }
+        }
+        registers[0]
+    }
+
+    // Evaluates the expression for every sample, where `inputs[i][j]` is the value of input `i` in sample `j`.
+    pub fn eval_slice(&self, inputs: &[&[f64]], output: &mut [f64]) {
+        assert_eq!(inputs.len(), self.inputs, "wrong number of inputs");
+        assert!(inputs.iter().all(|input| input.len() >= output.len()), "input slices are shorter than the output");
+
+        let mut registers = vec![[0.0; CHUNK]; self.registers];
+        for start in (0..output.len()).step_by(CHUNK) {
+            let len = CHUNK.min(output.len() - start);
+            for op in &self.ops {
+                self.step(*op, &mut registers, inputs, start, len);
+            }
+            output[start..start + len].copy_from_slice(&registers[0][..len]);
+        }
+    }
+
+    fn step(&self, op: Op, registers: &mut [[f64; CHUNK]], inputs: &[&[f64]], start: usize, len: usize) {
+        match op {
+            Op::Const(dst, value) => registers[dst][..len].fill(value),
+            Op::Load(dst, input) => registers[dst][..len].copy_from_slice(&inputs[input][start..start + len]),
+            Op::Neg(dst) => registers[dst][..len].iter_mut().for_each(|x| *x = -*x),
+            Op::Add(dst) => binary(registers, dst, len, |a, b| a + b),
+            Op::Sub(dst) => binary(registers, dst, len, |a, b| a - b),
+            Op::Mul(dst) => binary(registers, dst, len, |a, b| a * b),
+            Op::Div(dst) => binary(registers, dst, len, |a, b| a / b),
+            Op::Pow(dst) => binary(registers, dst, len, f64::powf),
+            Op::Unary(dst, f) => registers[dst][..len].iter_mut().for_each(|x| *x = f(*x)),
+            Op::Binary(dst, f) => binary(registers, dst, len, f),
+        }
+    }
+}
+
+#[inline(always)]
+fn binary(registers: &mut [[f64; CHUNK]], dst: usize, len: usize, f: impl Fn(f64, f64) -> f64) {
+    let (lhs, rhs) = registers.split_at_mut(dst + 1);
+    for (a, b) in lhs[dst][..len].iter_mut().zip(&rhs[0][..len]) {
+        *a = f(*a, *b);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::math::expr::{Env, Expr};
+
+    #[test]
+    fn single_evaluations_match_slices_and_the_tree() {
+        let inputs = ["x", "y"];
+        let xs = [-1.5, 0.0, 0.3, 2.0];
+        let ys = [0.5, -2.0, 1.0, 3.0];
+        for source in ["x + y", "x y - 2", "-x^2 / (1 + y^2)", "sin(x) cos(y) + ln(abs(y))", "max(x, y) - mod(y, x)", "(x + (y + (x + (y + (x + (y + (x + (y + (x + (y + (x + (y + (x + (y + (x + (y + x)))))))))))))))) * y"] {
+            let expr = Expr::parse(source).unwrap();
+            let bytecode = expr.compile(&inputs).unwrap();
+            let mut output = [0.0; 4];
+            bytecode.eval_slice(&[&xs, &ys], &mut output);
+            for k in 0..xs.len() {
+                let scalar = bytecode.eval(&[xs[k], ys[k]]);
+                let tree = expr.eval_with(&Env::new().with("x", xs[k]).with("y", ys[k]));
+                assert_eq!(scalar.to_bits(), output[k].to_bits(), "{} at ({}, {})", source, xs[k], ys[k]);
+                assert!(scalar == tree || (scalar.is_nan() && tree.is_nan()), "{} at ({}, {}): {} vs {}", source, xs[k], ys[k], scalar, tree);
+            }
+        }
+    }
+}
@@ -1,36 +1,165 @@
-#[derive(Clone, Copy)]
-struct Complex {
+use std::f64::consts::FRAC_PI_2;
+
+// Multivalued functions use their principal branch, with the branch cut along the negative real
+// axis for ln, sqrt and powers, so that arg(z) lies in (-pi, pi]. The inverse trigonometric
+// functions are defined through ln and sqrt and inherit their cuts: asin and acos are cut along the
+// real axis outside [-1, 1] and atan along the imaginary axis outside [-i, i].
+#[derive(Clone, Copy, PartialEq, Default, Debug)]
+pub struct Complex {
     // z = a + bi
-    a: f64,
-    b: f64,
+    pub a: f64,
+    pub b: f64,
 }
 
 impl Complex {
-    fn new(a: f64, b: f64) -> Complex {
+    pub const ZERO: Complex = Complex { a: 0.0, b: 0.0 };
+    pub const ONE: Complex = Complex { a: 1.0, b: 0.0 };
+    pub const I: Complex = Complex { a: 0.0, b: 1.0 };
+
+    pub const fn new(a: f64, b: f64) -> Complex {
         Complex { a, b }
     }
 
-    fn from_polar(r: f64, theta: f64) -> Complex {
+    pub fn from_polar(r: f64, theta: f64) -> Complex {
         Complex { a: r * theta.cos(), b: r * theta.sin() }
     }
 
-    fn abs(self) -> f64 {
-        ((self.a * self.a) + (self.b * self.b)).sqrt()
+    pub fn abs(self) -> f64 {
+        self.a.hypot(self.b)
+    }
+
+    pub fn norm_sqr(self) -> f64 {
+        (self.a * self.a) + (self.b * self.b)
     }
 
-    fn arg(self) -> f64 {
-        (self.b.atan2(self.a) + std::f64::consts::TAU) % (std::f64::consts::TAU)
+    pub fn arg(self) -> f64 {
+        self.b.atan2(self.a)
     }
 
-    fn con(self) -> Complex {
+    pub fn con(self) -> Complex {
         Complex { a: self.a, b: -self.b }
     }
 
-    fn pow(self, v: f64) -> Complex {
+    pub fn is_finite(self) -> bool {
+        self.a.is_finite() && self.b.is_finite()
+    }
+
+    pub fn is_nan(self) -> bool {
+        self.a.is_nan() || self.b.is_nan()
+    }
+
+    pub fn recip(self) -> Complex {
+        Complex::ONE / self
+    }
+
+    pub fn pow(self, v: f64) -> Complex {
+        if self == Complex::ZERO {
+            return if v > 0.0 { Complex::ZERO } else if v == 0.0 { Complex::ONE } else { Complex::new(f64::INFINITY, 0.0) };
+        }
         let ang: f64 = self.arg() * v;
         let rad: f64 = self.abs().powf(v);
         Complex::from_polar(rad, ang)
     }
+
+    // z^w = exp(w ln z), with 0^w = 0 for Re w > 0.
+    pub fn powc(self, w: Complex) -> Complex {
+        if w.b == 0.0 {
+            return self.pow(w.a);
+        }
+        if self == Complex::ZERO {
+            return if w.a > 0.0 { Complex::ZERO } else { Complex::new(f64::NAN, f64::NAN) };
+        }
+        (w * self.ln()).exp()
+    }
+
+    pub fn exp(self) -> Complex {
+        Complex::from_polar(self.a.exp(), self.b)
+    }
+
+    pub fn ln(self) -> Complex {
+        Complex { a: self.abs().ln(), b: self.arg() }
+    }
+
+    pub fn sqrt(self) -> Complex {
+        if self == Complex::ZERO {
+            return Complex::ZERO;
+        }
+        let r = self.abs();
+        if self.a >= 0.0 {
+            let t = ((r + self.a) / 2.0).sqrt();
+            Complex { a: t, b: self.b / (2.0 * t) }
+        } else {
+            let t = ((r - self.a) / 2.0).sqrt();
+            Complex { a: self.b.abs() / (2.0 * t), b: t.copysign(self.b) }
+        }
+    }
+
+    pub fn sin(self) -> Complex {
+        Complex { a: self.a.sin() * self.b.cosh(), b: self.a.cos() * self.b.sinh() }
+    }
+
+    pub fn cos(self) -> Complex {
+        Complex { a: self.a.cos() * self.b.cosh(), b: -self.a.sin() * self.b.sinh() }
+    }
+
+    // Written in terms of doubled angles and divided through by cosh(2b), so that once that overflows
+    // the result goes to its limit of +-i instead of inf / inf.
+    pub fn tan(self) -> Complex {
+        let cosh = (2.0 * self.b).cosh();
+        let den = 1.0 + (2.0 * self.a).cos() / cosh;
+        Complex { a: (2.0 * self.a).sin() / cosh / den, b: (2.0 * self.b).tanh() / den }
+    }
+
+    pub fn sinh(self) -> Complex {
+        Complex { a: self.a.sinh() * self.b.cos(), b: self.a.cosh() * self.b.sin() }
+    }
+
+    pub fn cosh(self) -> Complex {
+        Complex { a: self.a.cosh() * self.b.cos(), b: self.a.sinh() * self.b.sin() }
+    }
+
+    // Like tan, divided through by cosh(2a) so that it goes to +-1 for large real parts.
+    pub fn tanh(self) -> Complex {
+        let cosh = (2.0 * self.a).cosh();
+        let den = 1.0 + (2.0 * self.b).cos() / cosh;
+        Complex { a: (2.0 * self.a).tanh() / den, b: (2.0 * self.b).sin() / cosh / den }
+    }
+
+    // asin(z) = -i ln(iz + sqrt(1 - z^2))
+    pub fn asin(self) -> Complex {
+        -Complex::I * (Complex::I * self + (1.0 - self * self).sqrt()).ln()
+    }
+
+    // acos(z) = pi/2 - asin(z)
+    pub fn acos(self) -> Complex {
+        FRAC_PI_2 - self.asin()
+    }
+
+    // atan(z) = i/2 (ln(1 - iz) - ln(1 + iz))
+    pub fn atan(self) -> Complex {
+        let iz = Complex::I * self;
+        Complex::new(0.0, 0.5) * ((1.0 - iz).ln() - (1.0 + iz).ln())
+    }
+}
+
+impl From<f64> for Complex {
+    fn from(a: f64) -> Complex {
+        Complex { a, b: 0.0 }
+    }
+}
+
+impl From<(f64, f64)> for Complex {
+    fn from((a, b): (f64, f64)) -> Complex {
+        Complex { a, b }
+    }
+}
+
+impl std::ops::Neg for Complex {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        Complex { a: -self.a, b: -self.b }
+    }
 }
 
 impl std::ops::Add for Complex {
@@ -67,13 +196,233 @@ impl std::ops::Div for Complex {
         let den: f64 = (rhs.a * rhs.a) + (rhs.b * rhs.b);
         Complex {
             a: ((self.a * rhs.a) + (self.b * rhs.b)) / (den),
-            b: ((self.b * rhs.a) + (self.a * rhs.b)) / (den),
+            b: ((self.b * rhs.a) - (self.a * rhs.b)) / (den),
         }
     }
 }
 
+// Mixed arithmetic with real numbers, in both operand orders.
+macro_rules! real_ops {
+    ($($trait:ident $method:ident $assign_trait:ident $assign_method:ident),*) => {$(
+        impl std::ops::$trait<f64> for Complex {
+            type Output = Complex;
+
+            fn $method(self, rhs: f64) -> Complex {
+                std::ops::$trait::$method(self, Complex::from(rhs))
+            }
+        }
+
+        impl std::ops::$trait<Complex> for f64 {
+            type Output = Complex;
+
+            fn $method(self, rhs: Complex) -> Complex {
+                std::ops::$trait::$method(Complex::from(self), rhs)
+            }
+        }
+
+        impl std::ops::$assign_trait for Complex {
+            fn $assign_method(&mut self, rhs: Complex) {
+                *self = std::ops::$trait::$method(*self, rhs);
+            }
+        }
+
+        impl std::ops::$assign_trait<f64> for Complex {
+            fn $assign_method(&mut self, rhs: f64) {
+                *self = std::ops::$trait::$method(*self, rhs);
+            }
+        }
+    )*};
+}
+
+real_ops!(Add add AddAssign add_assign, Sub sub SubAssign sub_assign, Mul mul MulAssign mul_assign, Div div DivAssign div_assign);
+
 impl std::fmt::Display for Complex {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{} + {}i", self.a, self.b)
+        if self.b.is_sign_negative() {
+            write!(f, "{} - {}i", self.a, -self.b)
+        } else {
+            write!(f, "{} + {}i", self.a, self.b)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f64::consts::{PI, SQRT_2};
+
+    const EPS: f64 = 1e-12;
+
+    fn assert_close(actual: Complex, expected: Complex) {
+        let scale = expected.abs().max(1.0);
+        assert!((actual - expected).abs() < 1e-9 * scale, "expected {}, got {}", expected, actual);
+    }
+
+    #[test]
+    fn display() {
+        assert_eq!(Complex::new(3.0, -2.0).to_string(), "3 - 2i");
+        assert_eq!(Complex::new(-1.5, 0.25).to_string(), "-1.5 + 0.25i");
+        assert_eq!(Complex::new(0.0, -1.0).to_string(), "0 - 1i");
+        assert_eq!(Complex::new(0.0, 4.0).to_string(), "0 + 4i");
+        assert_eq!(Complex::new(3.0, 0.0).to_string(), "3 + 0i");
+        assert_eq!(Complex::new(3.0, -0.0).to_string(), "3 - 0i");
+        assert_eq!(Complex::from(-2.0).to_string(), "-2 + 0i");
+    }
+
+    #[test]
+    fn arithmetic() {
+        let (z, w) = (Complex::new(1.0, 2.0), Complex::new(3.0, -1.0));
+        assert_eq!(z + w, Complex::new(4.0, 1.0));
+        assert_eq!(z - w, Complex::new(-2.0, 3.0));
+        assert_eq!(z * w, Complex::new(5.0, 5.0));
+        assert_close(z / w, Complex::new(0.1, 0.7));
+        assert_eq!(-z, Complex::new(-1.0, -2.0));
+        assert_eq!(z.con(), Complex::new(1.0, -2.0));
+        assert_eq!(Complex::new(3.0, 4.0).abs(), 5.0);
+        assert_eq!(Complex::new(3.0, 4.0).norm_sqr(), 25.0);
+        assert_close(z * z.recip(), Complex::ONE);
+        assert_eq!(Complex::I * Complex::I, -Complex::ONE);
+    }
+
+    #[test]
+    fn mixed_real_operators() {
+        let z = Complex::new(1.0, 2.0);
+        assert_eq!(z + 2.0, Complex::new(3.0, 2.0));
+        assert_eq!(2.0 + z, Complex::new(3.0, 2.0));
+        assert_eq!(z - 2.0, Complex::new(-1.0, 2.0));
+        assert_eq!(2.0 - z, Complex::new(1.0, -2.0));
+        assert_eq!(z * 2.0, Complex::new(2.0, 4.0));
+        assert_eq!(2.0 * z, Complex::new(2.0, 4.0));
+        assert_eq!(z / 2.0, Complex::new(0.5, 1.0));
+        assert_close(5.0 / z, Complex::new(1.0, -2.0));
+
+        let mut w = z;
+        w += 1.0;
+        w -= Complex::I;
+        w *= 2.0;
+        w /= Complex::new(0.0, 2.0);
+        assert_close(w, Complex::new(1.0, -2.0));
+    }
+
+    #[test]
+    fn polar() {
+        assert_close(Complex::from_polar(2.0, PI / 2.0), Complex::new(0.0, 2.0));
+        assert_eq!(Complex::new(-1.0, 0.0).arg(), PI);
+        assert_eq!(Complex::new(0.0, -1.0).arg(), -PI / 2.0);
+    }
+
+    #[test]
+    fn exp_and_ln() {
+        assert_close(Complex::new(0.0, PI).exp(), -Complex::ONE);
+        assert_close(Complex::new(1.0, PI / 2.0).exp(), Complex::new(0.0, 1f64.exp()));
+        assert_close(Complex::I.ln(), Complex::new(0.0, PI / 2.0));
+        assert_close(Complex::new(-2.0, 0.0).ln(), Complex::new(2f64.ln(), PI));
+        let z = Complex::new(0.3, -1.7);
+        assert_close(z.ln().exp(), z);
+    }
+
+    #[test]
+    fn powers() {
+        assert_close(Complex::I.pow(2.0), -Complex::ONE);
+        assert_close(Complex::new(-8.0, 0.0).pow(1.0 / 3.0), Complex::new(1.0, 3f64.sqrt()));
+        assert_close(Complex::I.powc(Complex::I), Complex::from((-PI / 2.0).exp()));
+        assert_close(Complex::new(2.0, 0.0).powc(Complex::new(3.0, 0.0)), Complex::from(8.0));
+        let z = Complex::new(1.5, 0.5);
+        assert_close(z.powc(Complex::new(2.0, 1.0)), (Complex::new(2.0, 1.0) * z.ln()).exp());
+    }
+
+    #[test]
+    fn zero_to_the_w() {
+        assert_eq!(Complex::ZERO.pow(2.0), Complex::ZERO);
+        assert_eq!(Complex::ZERO.pow(0.0), Complex::ONE);
+        assert_eq!(Complex::ZERO.pow(-1.0).a, f64::INFINITY);
+        assert_eq!(Complex::ZERO.powc(Complex::new(1.0, 1.0)), Complex::ZERO);
+        assert_eq!(Complex::ZERO.powc(Complex::from(0.0)), Complex::ONE);
+        assert!(Complex::ZERO.powc(Complex::new(-1.0, 1.0)).is_nan());
+        assert!(Complex::ZERO.powc(Complex::new(0.0, 1.0)).is_nan());
+    }
+
+    #[test]
+    fn sqrt() {
+        assert_eq!(Complex::ZERO.sqrt(), Complex::ZERO);
+        assert_close(Complex::from(4.0).sqrt(), Complex::from(2.0));
+        assert_close(Complex::I.sqrt(), Complex::new(1.0, 1.0) / SQRT_2);
+        assert_close(Complex::new(3.0, -4.0).sqrt(), Complex::new(2.0, -1.0));
+        let z = Complex::new(-0.4, 2.3);
+        assert_close(z.sqrt() * z.sqrt(), z);
+    }
+
+    #[test]
+    fn trigonometric() {
+        let one = 1f64;
+        assert_close(Complex::I.sin(), Complex::new(0.0, one.sinh()));
+        assert_close(Complex::I.cos(), Complex::from(one.cosh()));
+        assert_close(Complex::I.tan(), Complex::new(0.0, one.tanh()));
+        assert_close(Complex::from(1.0).tan(), Complex::from(one.tan()));
+        assert_close(Complex::I.sinh(), Complex::new(0.0, one.sin()));
+        assert_close(Complex::I.cosh(), Complex::from(one.cos()));
+        assert_close(Complex::I.tanh(), Complex::new(0.0, one.tan()));
+        assert_close(Complex::from(1.0).tanh(), Complex::from(one.tanh()));
+
+        let z = Complex::new(0.7, -0.4);
+        assert_close(z.sin() * z.sin() + z.cos() * z.cos(), Complex::ONE);
+        assert_close(z.tan(), z.sin() / z.cos());
+        assert_close(z.tanh(), z.sinh() / z.cosh());
+        assert_close(z.sin().asin(), z);
+        assert_close(z.cos().acos(), z);
+        assert_close(z.tan().atan(), z);
+    }
+
+    #[test]
+    fn tan_and_tanh_far_from_the_real_axis() {
+        assert_close(Complex::new(0.0, 400.0).tan(), Complex::I);
+        assert_close(Complex::new(1.0, -400.0).tan(), -Complex::I);
+        assert_close(Complex::new(400.0, 0.0).tanh(), Complex::ONE);
+        assert_close(Complex::new(-400.0, 1.0).tanh(), -Complex::ONE);
+        assert_close(Complex::new(0.5, 30.0).tan(), Complex::I);
+    }
+
+    #[test]
+    fn inverse_trigonometric() {
+        assert_close(Complex::from(0.5).asin(), Complex::from(0.5f64.asin()));
+        assert_close(Complex::from(0.5).acos(), Complex::from(0.5f64.acos()));
+        assert_close(Complex::from(2.0).atan(), Complex::from(2f64.atan()));
+        assert_close(Complex::new(0.0, 0.5).atan(), Complex::new(0.0, 0.5f64.atanh()));
+    }
+
+    #[test]
+    fn sqrt_and_ln_branch_cut() {
+        // The negative real axis, approached from above and below.
+        assert_close(Complex::new(-4.0, EPS).sqrt(), Complex::new(0.0, 2.0));
+        assert_close(Complex::new(-4.0, -EPS).sqrt(), Complex::new(0.0, -2.0));
+        assert_close(Complex::new(-1.0, EPS).ln(), Complex::new(0.0, PI));
+        assert_close(Complex::new(-1.0, -EPS).ln(), Complex::new(0.0, -PI));
+        // On the cut itself, the upper side is taken.
+        assert_close(Complex::from(-4.0).sqrt(), Complex::new(0.0, 2.0));
+        assert_close(Complex::from(-1.0).ln(), Complex::new(0.0, PI));
+    }
+
+    #[test]
+    fn asin_and_acos_branch_cuts() {
+        let acosh2 = 2f64.acosh();
+        assert_close(Complex::new(2.0, EPS).asin(), Complex::new(PI / 2.0, acosh2));
+        assert_close(Complex::new(2.0, -EPS).asin(), Complex::new(PI / 2.0, -acosh2));
+        assert_close(Complex::new(-2.0, EPS).asin(), Complex::new(-PI / 2.0, acosh2));
+        assert_close(Complex::new(-2.0, -EPS).asin(), Complex::new(-PI / 2.0, -acosh2));
+
+        assert_close(Complex::new(2.0, EPS).acos(), Complex::new(0.0, -acosh2));
+        assert_close(Complex::new(2.0, -EPS).acos(), Complex::new(0.0, acosh2));
+        assert_close(Complex::new(-2.0, EPS).acos(), Complex::new(PI, -acosh2));
+        assert_close(Complex::new(-2.0, -EPS).acos(), Complex::new(PI, acosh2));
+    }
+
+    #[test]
+    fn atan_branch_cut() {
+        // The imaginary axis beyond +-i, approached from the right and the left.
+        let im = 0.5 * 3f64.ln();
+        assert_close(Complex::new(EPS, 2.0).atan(), Complex::new(PI / 2.0, im));
+        assert_close(Complex::new(-EPS, 2.0).atan(), Complex::new(-PI / 2.0, im));
+        assert_close(Complex::new(EPS, -2.0).atan(), Complex::new(PI / 2.0, -im));
+        assert_close(Complex::new(-EPS, -2.0).atan(), Complex::new(-PI / 2.0, -im));
     }
 }
@@ -0,0 +1,164 @@
+use super::{expr::Expr, func::Func};
+
+impl Expr {
+    pub fn depends_on(&self, var: &str) -> bool {
+        match self {
+            Expr::Constant(_) | Expr::ImaginaryUnit => false,
+            Expr::Variable(name) => name == var,
+            Expr::Neg(operand) => operand.depends_on(var),
+            Expr::Add(lhs, rhs) | Expr::Sub(lhs, rhs) | Expr::Mul(lhs, rhs) | Expr::Div(lhs, rhs) | Expr::Pow(lhs, rhs) => {
+                lhs.depends_on(var) || rhs.depends_on(var)
+            }
+            Expr::Call(_, args) => args.iter().any(|arg| arg.depends_on(var)),
+        }
+    }
+
+    pub fn derivative(&self, var: &str) -> Expr {
+        match self {
+            Expr::Constant(_) | Expr::ImaginaryUnit => Expr::Constant(0.0),
+            Expr::Variable(name) => Expr::Constant(if name == var { 1.0 } else { 0.0 }),
+            Expr::Neg(operand) => -operand.derivative(var),
+            Expr::Add(lhs, rhs) => Expr::add(lhs.derivative(var), rhs.derivative(var)),
+            Expr::Sub(lhs, rhs) => Expr::sub(lhs.derivative(var), rhs.derivative(var)),
+            Expr::Mul(lhs, rhs) => Expr::add(
+                Expr::mul(lhs.derivative(var), (**rhs).clone()),
+                Expr::mul((**lhs).clone(), rhs.derivative(var)),
+            ),
+            Expr::Div(lhs, rhs) => Expr::div(
+                Expr::sub(
+                    Expr::mul(lhs.derivative(var), (**rhs).clone()),
+                    Expr::mul((**lhs).clone(), rhs.derivative(var)),
+                ),
+                Expr::pow((**rhs).clone(), Expr::Constant(2.0)),
+            ),
+            Expr::Pow(base, exponent) => power_derivative(base, exponent, var),
+            Expr::Call(func, args) => call_derivative(*func, args, var),
+        }
+    }
+}
+
+fn power_derivative(base: &Expr, exponent: &Expr, var: &str) -> Expr {
+    if !exponent.depends_on(var) {
+        // d/dx u^c = c * u^(c - 1) * u'
+        Expr::mul(
+            Expr::mul(exponent.clone(), Expr::pow(base.clone(), Expr::sub(exponent.clone(), Expr::Constant(1.0)))),
+            base.derivative(var),
+        )
+    } else if !base.depends_on(var) {
+        // d/dx c^v = c^v * ln(c) * v'
+        Expr::mul(
+            Expr::mul(Expr::pow(base.clone(), exponent.clone()), ln(base.clone())),
+            exponent.derivative(var),
+        )
+    } else {
+        // d/dx u^v = u^v * (v' * ln(u) + v * u' / u)
+        Expr::mul(
+            Expr::pow(base.clone(), exponent.clone()),
+            Expr::add(
+                Expr::mul(exponent.derivative(var), ln(base.clone())),
+                Expr::div(Expr::mul(exponent.clone(), base.derivative(var)), base.clone()),
+            ),
+        )
+    }
+}
+
+fn call_derivative(func: Func, args: &[Expr], var: &str) -> Expr {
+    let u = || args[0].clone();
+    let du = || args[0].derivative(var);
+    let call = |func: Func, arg: Expr| Expr::call(func, vec![arg]);
+    let one = || Expr::Constant(1.0);
+    let square = |e: Expr| Expr::pow(e, Expr::Constant(2.0));
+
+    // Single argument functions are written as f'(u), then multiplied by u' for the chain rule.
+    let outer = match func {
+        Func::Sin => call(Func::Cos, u()),
+        Func::Cos => -call(Func::Sin, u()),
+        Func::Tan => Expr::div(one(), square(call(Func::Cos, u()))),
+        Func::Asin => Expr::div(one(), call(Func::Sqrt, Expr::sub(one(), square(u())))),
+        Func::Acos => -Expr::div(one(), call(Func::Sqrt, Expr::sub(one(), square(u())))),
+        Func::Atan => Expr::div(one(), Expr::add(one(), square(u()))),
+        Func::Sinh => call(Func::Cosh, u()),
+        Func::Cosh => call(Func::Sinh, u()),
+        Func::Tanh => Expr::div(one(), square(call(Func::Cosh, u()))),
+        Func::Exp => call(Func::Exp, u()),
+        Func::Ln => Expr::div(one(), u()),
+        Func::Sqrt => Expr::div(one(), Expr::mul(Expr::Constant(2.0), call(Func::Sqrt, u()))),
+        Func::Abs => Expr::div(u(), call(Func::Abs, u())),
+        Func::Floor | Func::Ceil => return Expr::Constant(0.0),
+        Func::Log => return Expr::div(ln(args[1].clone()), ln(args[0].clone())).derivative(var),
+        Func::Pow => return power_derivative(&args[0], &args[1], var),
+        Func::Min | Func::Max => return extremum_derivative(func, &args[0], &args[1], var),
+        Func::Mod => {
+            // mod(a, b) = a - b * floor(a / b), where floor is locally constant.
+            let quotient = Expr::call(Func::Floor, vec![Expr::div(args[0].clone(), args[1].clone())]);
+            return Expr::sub(args[0].derivative(var), Expr::mul(args[1].derivative(var), quotient));
+        }
+    };
+
+    Expr::mul(outer, du())
+}
+
+// min(a, b) = (a + b - |a - b|) / 2 and max(a, b) = (a + b + |a - b|) / 2, away from a = b.
+fn extremum_derivative(func: Func, a: &Expr, b: &Expr, var: &str) -> Expr {
+    let (da, db) = (a.derivative(var), b.derivative(var));
+    let difference = Expr::sub(a.clone(), b.clone());
+    let sign = Expr::div(difference.clone(), Expr::call(Func::Abs, vec![difference]));
+    let spread = Expr::mul(sign, Expr::sub(da.clone(), db.clone()));
+    let total = if func == Func::Min { Expr::sub(Expr::add(da, db), spread) } else { Expr::add(Expr::add(da, db), spread) };
+    Expr::div(total, Expr::Constant(2.0))
+}
+
+fn ln(arg: Expr) -> Expr {
+    Expr::call(Func::Ln, vec![arg])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::expr::Env;
+
+    // Compares the derivative in x, before and after simplifying, with a central difference at points
+    // away from the kinks of abs, floor, min and mod.
+    fn matches_finite_differences(source: &str) {
+        let expr = Expr::parse(source).unwrap();
+        let derivative = expr.derivative("x");
+        let simplified = derivative.simplify();
+        let h = 1e-6;
+        for &(x, y) in &[(0.3, 1.3), (0.45, 2.1), (0.7, 0.8)] {
+            let at = |x: f64| expr.eval_with(&Env::new().with("x", x).with("y", y));
+            let expected = (at(x + h) - at(x - h)) / (2.0 * h);
+            let env = Env::new().with("x", x).with("y", y);
+            for actual in [derivative.eval_with(&env), simplified.eval_with(&env)] {
+                assert!((actual - expected).abs() < 1e-6 * (1.0 + expected.abs()), "d/dx {} at ({}, {}): {} != {}", source, x, y, actual, expected);
+            }
+        }
+    }
+
+    #[test]
+    fn arithmetic_and_powers() {
+        for source in ["3x^2 - 2x + 1", "x y - y / x", "(x + y)^3", "1 / (1 + x^2)", "x^y", "y^x", "x^x", "2^-x", "-x^-2", "sqrt(x) x"] {
+            matches_finite_differences(source);
+        }
+    }
+
+    #[test]
+    fn functions() {
+        for source in ["sin(x y)", "cos(x^2)", "tan(x)", "asin(x)", "acos(x / 2)", "atan(3x)", "sinh(x)", "cosh(x y)", "tanh(2x)", "exp(-x^2)", "ln(1 + x)", "log(2, x + 1)", "log(x + 1, 3)"] {
+            matches_finite_differences(source);
+        }
+    }
+
+    #[test]
+    fn piecewise_functions() {
+        for source in ["abs(x - 0.5)", "floor(3x) + x", "ceil(x) x", "min(x, y)", "max(x^2, y)", "mod(5x, y + 3)", "pow(x, 3)"] {
+            matches_finite_differences(source);
+        }
+    }
+
+    #[test]
+    fn independent_variables() {
+        let expr = Expr::parse("sin(y) + 2^y").unwrap();
+        assert!(!expr.depends_on("x"));
+        assert_eq!(expr.derivative("x").simplify(), Expr::Constant(0.0));
+    }
+}
@@ -1,23 +1,137 @@
-#[derive(Clone)]
+use super::{
+    complex::Complex,
+    func::{Func, MAX_ARITY},
+};
+
+#[derive(Clone, PartialEq, Debug)]
 pub enum Expr {
     Constant(f64),
+    ImaginaryUnit,
+    Variable(String),
+    Neg(Box<Expr>),
     Add(Box<Expr>, Box<Expr>),
     Sub(Box<Expr>, Box<Expr>),
     Mul(Box<Expr>, Box<Expr>),
     Div(Box<Expr>, Box<Expr>),
+    Pow(Box<Expr>, Box<Expr>),
+    Call(Func, Vec<Expr>),
+}
+
+// Values for the variables of an expression, by name. Evaluators over other number types, such as
+// intervals, use the same environment with a different `T`.
+#[derive(Clone)]
+pub struct Env<'a, T = f64> {
+    variables: Vec<(&'a str, T)>,
+}
+
+impl<T> Default for Env<'_, T> {
+    fn default() -> Self {
+        Env { variables: Vec::new() }
+    }
+}
+
+impl<'a, T: Copy> Env<'a, T> {
+    pub fn new() -> Self {
+        Env { variables: Vec::new() }
+    }
+
+    pub fn with(mut self, name: &'a str, value: T) -> Self {
+        self.set(name, value);
+        self
+    }
+
+    pub fn set(&mut self, name: &'a str, value: T) {
+        match self.variables.iter_mut().find(|(n, _)| *n == name) {
+            Some((_, v)) => *v = value,
+            None => self.variables.push((name, value)),
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<T> {
+        self.variables.iter().find(|(n, _)| *n == name).map(|&(_, v)| v)
+    }
 }
 
 impl Expr {
     pub fn eval(&self) -> f64 {
+        self.eval_with(&Env::new())
+    }
+
+    // Variables missing from the environment evaluate to NaN, which plotting code treats as a hole.
+    pub fn eval_with(&self, env: &Env) -> f64 {
         match self {
             &Expr::Constant(value) => value,
-            Expr::Add(lhs, rhs) => lhs.eval() + rhs.eval(),
-            Expr::Sub(lhs, rhs) => lhs.eval() - rhs.eval(),
-            Expr::Mul(lhs, rhs) => lhs.eval() * rhs.eval(),
-            Expr::Div(lhs, rhs) => lhs.eval() / rhs.eval(),
+            Expr::ImaginaryUnit => f64::NAN,
+            Expr::Variable(name) => env.get(name).unwrap_or(f64::NAN),
+            Expr::Neg(operand) => -operand.eval_with(env),
+            Expr::Add(lhs, rhs) => lhs.eval_with(env) + rhs.eval_with(env),
+            Expr::Sub(lhs, rhs) => lhs.eval_with(env) - rhs.eval_with(env),
+            Expr::Mul(lhs, rhs) => lhs.eval_with(env) * rhs.eval_with(env),
+            Expr::Div(lhs, rhs) => lhs.eval_with(env) / rhs.eval_with(env),
+            Expr::Pow(lhs, rhs) => lhs.eval_with(env).powf(rhs.eval_with(env)),
+            Expr::Call(func, args) => {
+                let mut values = [0.0; MAX_ARITY];
+                for (value, arg) in values.iter_mut().zip(args) {
+                    *value = arg.eval_with(env);
+                }
+                func.eval(&values[..args.len()])
+            }
         }
     }
 
+    // Evaluates over the complex numbers, using the branch cuts documented on `Complex`.
+    pub fn eval_complex(&self, env: &Env<Complex>) -> Complex {
+        match self {
+            &Expr::Constant(value) => Complex::from(value),
+            Expr::ImaginaryUnit => Complex::I,
+            Expr::Variable(name) => env.get(name).unwrap_or(Complex::new(f64::NAN, f64::NAN)),
+            // 0 - z rather than -z, so that negated reals like -1 have a +0 imaginary part and stay on
+            // the upper side of the branch cuts, where the principal values are.
+            Expr::Neg(operand) => Complex::ZERO - operand.eval_complex(env),
+            Expr::Add(lhs, rhs) => lhs.eval_complex(env) + rhs.eval_complex(env),
+            Expr::Sub(lhs, rhs) => lhs.eval_complex(env) - rhs.eval_complex(env),
+            Expr::Mul(lhs, rhs) => lhs.eval_complex(env) * rhs.eval_complex(env),
+            Expr::Div(lhs, rhs) => lhs.eval_complex(env) / rhs.eval_complex(env),
+            Expr::Pow(lhs, rhs) => lhs.eval_complex(env).powc(rhs.eval_complex(env)),
+            Expr::Call(func, args) => {
+                let mut values = [Complex::ZERO; MAX_ARITY];
+                for (value, arg) in values.iter_mut().zip(args) {
+                    *value = arg.eval_complex(env);
+                }
+                func.eval_complex(&values[..args.len()])
+            }
+        }
+    }
+
+    pub fn variables(&self) -> Vec<String> {
+        let mut variables = Vec::new();
+        self.collect_variables(&mut variables);
+        variables.sort();
+        variables.dedup();
+        variables
+    }
+
+    fn collect_variables(&self, variables: &mut Vec<String>) {
+        match self {
+            Expr::Constant(_) | Expr::ImaginaryUnit => {}
+            Expr::Variable(name) => variables.push(name.clone()),
+            Expr::Neg(operand) => operand.collect_variables(variables),
+            Expr::Add(lhs, rhs) | Expr::Sub(lhs, rhs) | Expr::Mul(lhs, rhs) | Expr::Div(lhs, rhs) | Expr::Pow(lhs, rhs) => {
+                lhs.collect_variables(variables);
+                rhs.collect_variables(variables);
+            }
+            Expr::Call(_, args) => {
+                for arg in args {
+                    arg.collect_variables(variables);
+                }
+            }
+        }
+    }
+
+    pub fn variable(name: &str) -> Expr {
+        Expr::Variable(name.to_string())
+    }
+
     pub fn add(lhs: Expr, rhs: Expr) -> Expr {
         Expr::Add(Box::new(lhs), Box::new(rhs))
     }
@@ -33,16 +147,83 @@ impl Expr {
     pub fn div(lhs: Expr, rhs: Expr) -> Expr {
         Expr::Div(Box::new(lhs), Box::new(rhs))
     }
+
+    pub fn pow(lhs: Expr, rhs: Expr) -> Expr {
+        Expr::Pow(Box::new(lhs), Box::new(rhs))
+    }
+
+    pub fn call(func: Func, args: Vec<Expr>) -> Expr {
+        assert_eq!(args.len(), func.arity(), "wrong number of arguments to {}", func.name());
+        Expr::Call(func, args)
+    }
+}
+
+impl Expr {
+    // Binding strength when printed, matching the parser's grammar levels.
+    fn precedence(&self) -> u8 {
+        match self {
+            Expr::Add(..) | Expr::Sub(..) => 1,
+            Expr::Mul(..) | Expr::Div(..) => 2,
+            Expr::Neg(_) => 3,
+            &Expr::Constant(value) if value.is_sign_negative() => 3,
+            Expr::Pow(..) => 4,
+            Expr::Constant(_) | Expr::ImaginaryUnit | Expr::Variable(_) | Expr::Call(..) => 5,
+        }
+    }
+
+    fn fmt_operand(&self, f: &mut std::fmt::Formatter<'_>, precedence: u8) -> std::fmt::Result {
+        if self.precedence() < precedence {
+            write!(f, "({})", self)
+        } else {
+            write!(f, "{}", self)
+        }
+    }
+
+    fn fmt_binary(f: &mut std::fmt::Formatter<'_>, lhs: &Expr, operator: &str, rhs: &Expr, precedence: u8) -> std::fmt::Result {
+        lhs.fmt_operand(f, precedence)?;
+        write!(f, "{}", operator)?;
+        rhs.fmt_operand(f, precedence + 1)
+    }
+}
+
+impl std::ops::Neg for Expr {
+    type Output = Expr;
+
+    fn neg(self) -> Expr {
+        Expr::Neg(Box::new(self))
+    }
 }
 
 impl std::fmt::Display for Expr {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             &Expr::Constant(value) => write!(f, "{}", value),
-            Expr::Add(lhs, rhs) => write!(f, "({} + {})", lhs, rhs),
-            Expr::Sub(lhs, rhs) => write!(f, "({} - {})", lhs, rhs),
-            Expr::Mul(lhs, rhs) => write!(f, "({} * {})", lhs, rhs),
-            Expr::Div(lhs, rhs) => write!(f, "({} / {})", lhs, rhs),
+            Expr::ImaginaryUnit => write!(f, "i"),
+            Expr::Variable(name) => write!(f, "{}", name),
+            Expr::Neg(operand) => {
+                write!(f, "-")?;
+                operand.fmt_operand(f, 3)
+            }
+            Expr::Add(lhs, rhs) => Expr::fmt_binary(f, lhs, " + ", rhs, 1),
+            Expr::Sub(lhs, rhs) => Expr::fmt_binary(f, lhs, " - ", rhs, 1),
+            Expr::Mul(lhs, rhs) => Expr::fmt_binary(f, lhs, " * ", rhs, 2),
+            Expr::Div(lhs, rhs) => Expr::fmt_binary(f, lhs, " / ", rhs, 2),
+            // Powers are right associative and take a signed exponent, as in "x^-y^2".
+            Expr::Pow(lhs, rhs) => {
+                lhs.fmt_operand(f, 5)?;
+                write!(f, "^")?;
+                rhs.fmt_operand(f, 3)
+            }
+            Expr::Call(func, args) => {
+                write!(f, "{}(", func.name())?;
+                for (i, arg) in args.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", arg)?;
+                }
+                write!(f, ")")
+            }
         }
     }
 }
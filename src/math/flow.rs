@@ -0,0 +1,113 @@
+use super::{bytecode::Bytecode, expr::Expr, ode};
+
+// A vector field F(x, y, z), compiled for tracing paths through it.
+pub struct VectorField {
+    components: [Bytecode; 3],
+}
+
+impl VectorField {
+    pub fn new(exprs: [&Expr; 3]) -> Result<VectorField, String> {
+        let [x, y, z] = exprs.map(|expr| expr.compile(&["x", "y", "z"]));
+        Ok(VectorField { components: [x?, y?, z?] })
+    }
+
+    pub fn eval(&self, point: [f64; 3]) -> [f64; 3] {
+        self.components.each_ref().map(|component| component.eval(&point))
+    }
+
+    pub fn speed(&self, point: [f64; 3]) -> f64 {
+        let [x, y, z] = self.eval(point);
+        (x * x + y * y + z * z).sqrt()
+    }
+
+    // Moves a particle at `point` along the field for the time `dt`, with one step of the classic
+    // fourth order Runge-Kutta method.
+    pub fn advect(&self, point: [f64; 3], dt: f64) -> [f64; 3] {
+        rk4_step(|p| self.eval(p), point, dt)
+    }
+
+    // The streamline through `seed`, traced both ways in steps of `step_length` until it leaves the cube
+    // [min, max]^3, runs into a point where the field vanishes or isn't defined, or `max_steps` are taken
+    // in either direction. The points are in order along the flow, and a closed streamline ends with the
+    // point it starts with.
+    pub fn streamline(&self, seed: [f64; 3], step_length: f64, max_steps: usize, min: f64, max: f64) -> Vec<[f64; 3]> {
+        // Following F / |F| instead of F spaces the points evenly, however fast the flow is.
+        let direction = |p: [f64; 3], sign: f64| {
+            let v = self.eval(p);
+            let speed = (v[0] * v[0] + v[1] * v[1] + v[2] * v[2]).sqrt();
+            v.map(|c| sign * c / speed)
+        };
+        let inside = |p: &[f64; 3]| p.iter().all(|c| c.is_finite() && (min..=max).contains(c));
+
+        let mut halves = [Vec::new(), Vec::new()];
+        for (half, sign) in halves.iter_mut().zip([1.0, -1.0]) {
+            let mut point = seed;
+            for step in 0..max_steps {
+                if self.speed(point) < 1e-9 {
+                    break;
+                }
+                point = rk4_step(|p| direction(p, sign), point, step_length);
+                if !inside(&point) {
+                    break;
+                }
+                // A closed orbit ends where it started, instead of going around again and again.
+                let distance = point.iter().zip(&seed).map(|(a, b)| (a - b) * (a - b)).sum::<f64>().sqrt();
+                if sign > 0.0 && step > 2 && distance < step_length {
+                    let mut points = vec![seed];
+                    points.append(half);
+                    points.push(seed);
+                    return points;
+                }
+                half.push(point);
+            }
+        }
+
+        let [forward, backward] = halves;
+        let mut points: Vec<[f64; 3]> = backward.into_iter().rev().collect();
+        points.push(seed);
+        points.extend(forward);
+        points
+    }
+}
+
+// Steps a point through a time-independent field with `ode::rk4_step`.
+fn rk4_step(f: impl Fn([f64; 3]) -> [f64; 3], p: [f64; 3], h: f64) -> [f64; 3] {
+    let next = ode::rk4_step(|_, y| f([y[0], y[1], y[2]]).to_vec(), 0.0, &p, h);
+    [next[0], next[1], next[2]]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn field(x: &str, y: &str, z: &str) -> VectorField {
+        let exprs = [x, y, z].map(|source| Expr::parse(source).unwrap());
+        VectorField::new([&exprs[0], &exprs[1], &exprs[2]]).unwrap()
+    }
+
+    #[test]
+    fn closed_orbits_end_at_the_seed() {
+        let points = field("-y", "x", "0").streamline([1.0, 0.0, 0.5], 0.1, 1000, -2.0, 2.0);
+        assert_eq!(points.first(), Some(&[1.0, 0.0, 0.5]));
+        assert_eq!(points.last(), Some(&[1.0, 0.0, 0.5]));
+        // Once around the unit circle, rather than max_steps around it.
+        assert!((60..=66).contains(&points.len()), "{} points", points.len());
+        assert!(points.iter().all(|p| ((p[0] * p[0] + p[1] * p[1]).sqrt() - 1.0).abs() < 1e-6 && p[2] == 0.5));
+    }
+
+    #[test]
+    fn open_streamlines_leave_the_cube() {
+        let points = field("1", "0", "0").streamline([0.05, 0.3, 0.0], 0.1, 1000, -1.0, 1.0);
+        assert_eq!(points.len(), 20);
+        assert!(points.windows(2).all(|w| (w[1][0] - w[0][0] - 0.1).abs() < 1e-9));
+        assert!((points[0][0] + 0.95).abs() < 1e-9 && (points[19][0] - 0.95).abs() < 1e-9);
+    }
+
+    #[test]
+    fn stops_where_the_field_vanishes() {
+        assert_eq!(field("0", "0", "0").streamline([0.5, 0.5, 0.5], 0.1, 1000, -1.0, 1.0), vec![[0.5, 0.5, 0.5]]);
+        // The field is zero on the plane z = 0, so the seed there doesn't go anywhere.
+        assert_eq!(field("z", "z", "z").streamline([0.5, -0.5, 0.0], 0.1, 1000, -1.0, 1.0), vec![[0.5, -0.5, 0.0]]);
+        assert_eq!(field("sqrt(-1)", "0", "0").streamline([0.0; 3], 0.1, 1000, -1.0, 1.0), vec![[0.0; 3]]);
+    }
+}
@@ -0,0 +1,136 @@
+use enum_ordinalize::Ordinalize;
+
+use super::complex::Complex;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Ordinalize)]
+pub enum Func {
+    Sin,
+    Cos,
+    Tan,
+    Asin,
+    Acos,
+    Atan,
+    Sinh,
+    Cosh,
+    Tanh,
+    Exp,
+    Ln,
+    Log,
+    Sqrt,
+    Pow,
+    Abs,
+    Floor,
+    Ceil,
+    Min,
+    Max,
+    Mod,
+}
+
+pub const MAX_ARITY: usize = 2;
+
+#[derive(Clone, Copy)]
+pub enum Kernel {
+    Unary(fn(f64) -> f64),
+    Binary(fn(f64, f64) -> f64),
+}
+
+impl Func {
+    pub fn from_name(name: &str) -> Option<Func> {
+        Func::VARIANTS.iter().copied().find(|func| func.name() == name)
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Func::Sin => "sin",
+            Func::Cos => "cos",
+            Func::Tan => "tan",
+            Func::Asin => "asin",
+            Func::Acos => "acos",
+            Func::Atan => "atan",
+            Func::Sinh => "sinh",
+            Func::Cosh => "cosh",
+            Func::Tanh => "tanh",
+            Func::Exp => "exp",
+            Func::Ln => "ln",
+            Func::Log => "log",
+            Func::Sqrt => "sqrt",
+            Func::Pow => "pow",
+            Func::Abs => "abs",
+            Func::Floor => "floor",
+            Func::Ceil => "ceil",
+            Func::Min => "min",
+            Func::Max => "max",
+            Func::Mod => "mod",
+        }
+    }
+
+    pub fn arity(self) -> usize {
+        match self.kernel() {
+            Kernel::Unary(_) => 1,
+            Kernel::Binary(_) => 2,
+        }
+    }
+
+    // `args` must hold exactly `arity()` values.
+    pub fn eval(self, args: &[f64]) -> f64 {
+        match self.kernel() {
+            Kernel::Unary(f) => f(args[0]),
+            Kernel::Binary(f) => f(args[0], args[1]),
+        }
+    }
+
+    // Functions without a natural complex extension are defined so that they agree with the real
+    // versions on the real axis: floor and ceil round both parts, min and max compare real parts,
+    // and mod is built from the rounded quotient.
+    pub fn eval_complex(self, args: &[Complex]) -> Complex {
+        let floor = |z: Complex| Complex::new(z.a.floor(), z.b.floor());
+        match self {
+            Func::Sin => args[0].sin(),
+            Func::Cos => args[0].cos(),
+            Func::Tan => args[0].tan(),
+            Func::Asin => args[0].asin(),
+            Func::Acos => args[0].acos(),
+            Func::Atan => args[0].atan(),
+            Func::Sinh => args[0].sinh(),
+            Func::Cosh => args[0].cosh(),
+            Func::Tanh => args[0].tanh(),
+            Func::Exp => args[0].exp(),
+            Func::Ln => args[0].ln(),
+            Func::Log => args[1].ln() / args[0].ln(),
+            Func::Sqrt => args[0].sqrt(),
+            Func::Pow => args[0].powc(args[1]),
+            Func::Abs => Complex::from(args[0].abs()),
+            Func::Floor => floor(args[0]),
+            Func::Ceil => Complex::new(args[0].a.ceil(), args[0].b.ceil()),
+            Func::Min => if args[1].a < args[0].a { args[1] } else { args[0] },
+            Func::Max => if args[1].a > args[0].a { args[1] } else { args[0] },
+            Func::Mod => args[0] - args[1] * floor(args[0] / args[1]),
+        }
+    }
+
+    pub fn kernel(self) -> Kernel {
+        match self {
+            Func::Sin => Kernel::Unary(f64::sin),
+            Func::Cos => Kernel::Unary(f64::cos),
+            Func::Tan => Kernel::Unary(f64::tan),
+            Func::Asin => Kernel::Unary(f64::asin),
+            Func::Acos => Kernel::Unary(f64::acos),
+            Func::Atan => Kernel::Unary(f64::atan),
+            Func::Sinh => Kernel::Unary(f64::sinh),
+            Func::Cosh => Kernel::Unary(f64::cosh),
+            Func::Tanh => Kernel::Unary(f64::tanh),
+            Func::Exp => Kernel::Unary(f64::exp),
+            Func::Ln => Kernel::Unary(f64::ln),
+            Func::Log => Kernel::Binary(|base, x| x.ln() / base.ln()),
+            Func::Sqrt => Kernel::Unary(f64::sqrt),
+            Func::Pow => Kernel::Binary(f64::powf),
+            Func::Abs => Kernel::Unary(f64::abs),
+            Func::Floor => Kernel::Unary(f64::floor),
+            Func::Ceil => Kernel::Unary(f64::ceil),
+            Func::Min => Kernel::Binary(f64::min),
+            Func::Max => Kernel::Binary(f64::max),
+            // Floored modulo, so the result takes the sign of the divisor like in most calculators.
+            Func::Mod => Kernel::Binary(|a, b| a - b * (a / b).floor()),
+        }
+    }
+}
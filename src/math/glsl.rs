@@ -0,0 +1,109 @@
+use super::{expr::Expr, func::Func};
+
+// Helpers referenced by generated code. GLSL's pow is undefined for negative bases, so powers go
+// through expr_pow, which follows f64::powf for integer exponents.
+pub const GLSL_PRELUDE: &str = "
+float expr_pow(float x, float y) {
+    if (x >= 0.0) return pow(x, y);
+    if (floor(y) != y) return intBitsToFloat(0x7fc00000);
+    float r = pow(-x, y);
+    return mod(y, 2.0) == 0.0 ? r : -r;
+}
+";
+
+impl Expr {
+    // Translates the expression to a GLSL float expression, with `variables` mapping each variable
+    // name to the GLSL identifier that holds its value.
+    pub fn to_glsl(&self, variables: &[(&str, &str)]) -> Result<String, String> {
+        let mut out = String::new();
+        self.write_glsl(variables, &mut out)?;
+        Ok(out)
+    }
+
+    fn write_glsl(&self, variables: &[(&str, &str)], out: &mut String) -> Result<(), String> {
+        let binary = |lhs: &Expr, operator: &str, rhs: &Expr, out: &mut String| {
+            out.push('(');
+            lhs.write_glsl(variables, out)?;
+            out.push_str(operator);
+            rhs.write_glsl(variables, out)?;
+            out.push(')');
+            Ok::<(), String>(())
+        };
+
+        match self {
+            &Expr::Constant(value) => out.push_str(&glsl_float(value)),
+            Expr::ImaginaryUnit => return Err("the imaginary unit can only be used in complex plots".to_string()),
+            Expr::Variable(name) => match variables.iter().find(|(variable, _)| variable == name) {
+                Some((_, identifier)) => out.push_str(identifier),
+                None => {
+                    let names: Vec<&str> = variables.iter().map(|(name, _)| *name).collect();
+                    return Err(format!("unknown variable '{}', expected one of {}", name, names.join(", ")));
+                }
+            },
+            Expr::Neg(operand) => {
+                out.push_str("(-");
+                operand.write_glsl(variables, out)?;
+                out.push(')');
+            }
+            Expr::Add(lhs, rhs) => binary(lhs, " + ", rhs, out)?,
+            Expr::Sub(lhs, rhs) => binary(lhs, " - ", rhs, out)?,
+            Expr::Mul(lhs, rhs) => binary(lhs, " * ", rhs, out)?,
+            Expr::Div(lhs, rhs) => binary(lhs, " / ", rhs, out)?,
+            Expr::Pow(lhs, rhs) => return Expr::call(Func::Pow, vec![(**lhs).clone(), (**rhs).clone()]).write_glsl(variables, out),
+            Expr::Call(Func::Log, args) => {
+                let ln = |arg: &Expr| Expr::call(Func::Ln, vec![arg.clone()]);
+                return Expr::div(ln(&args[1]), ln(&args[0])).write_glsl(variables, out);
+            }
+            Expr::Call(func, args) => {
+                out.push_str(glsl_function(*func));
+                out.push('(');
+                for (i, arg) in args.iter().enumerate() {
+                    if i > 0 {
+                        out.push_str(", ");
+                    }
+                    arg.write_glsl(variables, out)?;
+                }
+                out.push(')');
+            }
+        }
+        Ok(())
+    }
+}
+
+fn glsl_function(func: Func) -> &'static str {
+    match func {
+        Func::Ln => "log",
+        Func::Pow => "expr_pow",
+        // GLSL's mod is also the floored modulo.
+        func => func.name(),
+    }
+}
+
+fn glsl_float(value: f64) -> String {
+    // Constants beyond the range of f32 only become infinite in the cast.
+    let value = value as f32;
+    if value.is_nan() {
+        "intBitsToFloat(0x7fc00000)".to_string()
+    } else if value.is_infinite() {
+        if value > 0.0 { "intBitsToFloat(0x7f800000)" } else { "(-intBitsToFloat(0x7f800000))" }.to_string()
+    } else if value < 0.0 {
+        format!("({:?})", value)
+    } else {
+        format!("{:?}", value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn float_literals() {
+        assert_eq!(glsl_float(2.0), "2.0");
+        assert_eq!(glsl_float(-0.5), "(-0.5)");
+        assert_eq!(glsl_float(f64::NAN), "intBitsToFloat(0x7fc00000)");
+        assert_eq!(glsl_float(1e300), "intBitsToFloat(0x7f800000)");
+        assert_eq!(glsl_float(-1e300), "(-intBitsToFloat(0x7f800000))");
+        assert_eq!(glsl_float(f64::NEG_INFINITY), "(-intBitsToFloat(0x7f800000))");
+    }
+}
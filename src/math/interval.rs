@@ -0,0 +1,392 @@
+use std::f64::consts::{FRAC_PI_2, PI, TAU};
+
+use super::{
+    expr::{Env, Expr},
+    func::Func,
+};
+
+// A set of values [lo, hi] that is guaranteed to contain every value an expression takes over a box
+// of inputs. Bounds are rounded outwards by one ulp after every operation to absorb rounding errors.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Interval {
+    pub lo: f64,
+    pub hi: f64,
+    // Part of the box lies outside the domain, like ln over an interval containing negative numbers.
+    pub partial: bool,
+    // The expression may have a pole or a jump inside the box, like 1/x over an interval containing 0.
+    pub discontinuous: bool,
+}
+
+impl Interval {
+    pub const EMPTY: Interval = Interval { lo: f64::INFINITY, hi: f64::NEG_INFINITY, partial: true, discontinuous: false };
+    pub const ENTIRE: Interval = Interval { lo: f64::NEG_INFINITY, hi: f64::INFINITY, partial: false, discontinuous: false };
+
+    pub fn new(lo: f64, hi: f64) -> Interval {
+        Interval { lo: lo.min(hi), hi: lo.max(hi), partial: false, discontinuous: false }
+    }
+
+    pub fn point(value: f64) -> Interval {
+        Interval::new(value, value)
+    }
+
+    // No point of the box is inside the domain.
+    pub fn is_empty(&self) -> bool {
+        self.lo > self.hi
+    }
+
+    pub fn contains(&self, value: f64) -> bool {
+        self.lo <= value && value <= self.hi
+    }
+
+    pub fn width(&self) -> f64 {
+        if self.is_empty() { 0.0 } else { self.hi - self.lo }
+    }
+
+    pub fn midpoint(&self) -> f64 {
+        0.5 * (self.lo + self.hi)
+    }
+
+    pub fn split(&self) -> (Interval, Interval) {
+        let mid = self.midpoint();
+        (Interval { hi: mid, ..*self }, Interval { lo: mid, ..*self })
+    }
+
+    // Carries the flags of `self` and `other` over to a result.
+    fn inherit(self, other: Interval) -> Interval {
+        Interval {
+            partial: self.partial || other.partial,
+            discontinuous: self.discontinuous || other.discontinuous,
+            ..self
+        }
+    }
+
+    fn rounded(lo: f64, hi: f64) -> Interval {
+        if lo.is_nan() || hi.is_nan() {
+            return Interval::ENTIRE;
+        }
+        Interval::new(lo.next_down(), hi.next_up())
+    }
+
+    fn map(self, lo: f64, hi: f64) -> Interval {
+        if self.is_empty() {
+            return self;
+        }
+        Interval::rounded(lo, hi).inherit(self)
+    }
+
+    fn increasing(self, f: fn(f64) -> f64) -> Interval {
+        self.map(f(self.lo), f(self.hi))
+    }
+
+    fn decreasing(self, f: fn(f64) -> f64) -> Interval {
+        self.map(f(self.hi), f(self.lo))
+    }
+
+    // Restricts the interval to the domain [lo, hi], flagging the result if anything was cut off.
+    fn restrict(self, lo: f64, hi: f64) -> Interval {
+        if self.is_empty() || self.hi < lo || self.lo > hi {
+            return Interval { partial: true, ..Interval::EMPTY }.inherit(self);
+        }
+        Interval {
+            lo: self.lo.max(lo),
+            hi: self.hi.min(hi),
+            partial: self.partial || self.lo < lo || self.hi > hi,
+            discontinuous: self.discontinuous,
+        }
+    }
+
+    fn discontinuous(self) -> Interval {
+        Interval { discontinuous: true, ..self }
+    }
+
+    pub fn pow(self, rhs: Interval) -> Interval {
+        binary(self, rhs, |a, b| {
+            if b.lo == b.hi && b.lo.fract() == 0.0 {
+                a.powi(b.lo)
+            } else {
+                let a = a.restrict(0.0, f64::INFINITY);
+                if a.is_empty() {
+                    return a;
+                }
+                let corners = [a.lo.powf(b.lo), a.lo.powf(b.hi), a.hi.powf(b.lo), a.hi.powf(b.hi)];
+                let pole = a.lo == 0.0 && b.lo < 0.0;
+                let result = a.map(corners.iter().copied().fold(f64::INFINITY, f64::min), corners.iter().copied().fold(f64::NEG_INFINITY, f64::max));
+                if pole { result.discontinuous() } else { result }
+            }
+        })
+    }
+
+    fn powi(self, n: f64) -> Interval {
+        if n == 0.0 {
+            self.map(1.0, 1.0)
+        } else if n < 0.0 {
+            Interval::point(1.0) / self.powi(-n)
+        } else if n % 2.0 == 0.0 {
+            let magnitude = self.abs();
+            magnitude.map(magnitude.lo.powf(n), magnitude.hi.powf(n))
+        } else {
+            self.map(self.lo.powf(n), self.hi.powf(n))
+        }
+    }
+
+    pub fn abs(self) -> Interval {
+        if self.lo >= 0.0 {
+            self
+        } else if self.hi <= 0.0 {
+            -self
+        } else {
+            Interval { lo: 0.0, hi: self.hi.max(-self.lo), ..self }
+        }
+    }
+
+    // Bounds of sin over the interval, found by checking which extrema lie inside it.
+    pub fn sin(self) -> Interval {
+        if self.is_empty() {
+            return self;
+        }
+        if self.width() >= TAU {
+            return self.map(-1.0, 1.0);
+        }
+        let contains_shifted = |shift: f64| {
+            let k = ((self.lo - shift) / TAU).ceil();
+            shift + k * TAU <= self.hi
+        };
+        let lo = if contains_shifted(-FRAC_PI_2) { -1.0 } else { self.lo.sin().min(self.hi.sin()) };
+        let hi = if contains_shifted(FRAC_PI_2) { 1.0 } else { self.lo.sin().max(self.hi.sin()) };
+        self.map(lo.max(-1.0), hi.min(1.0))
+    }
+
+    pub fn cos(self) -> Interval {
+        Interval { lo: self.lo + FRAC_PI_2, hi: self.hi + FRAC_PI_2, ..self }.widen().sin()
+    }
+
+    pub fn tan(self) -> Interval {
+        if self.is_empty() {
+            return self;
+        }
+        // Poles sit at pi/2 + k * pi.
+        let k = ((self.lo - FRAC_PI_2) / PI).ceil();
+        if FRAC_PI_2 + k * PI <= self.hi {
+            Interval { partial: true, discontinuous: true, ..Interval::ENTIRE }.inherit(self)
+        } else {
+            self.increasing(f64::tan)
+        }
+    }
+
+    pub fn cosh(self) -> Interval {
+        self.abs().increasing(f64::cosh)
+    }
+
+    pub fn floor(self) -> Interval {
+        let result = self.increasing(f64::floor);
+        if !self.is_empty() && self.lo.floor() != self.hi.floor() { result.discontinuous() } else { result }
+    }
+
+    pub fn ceil(self) -> Interval {
+        let result = self.increasing(f64::ceil);
+        if !self.is_empty() && self.lo.ceil() != self.hi.ceil() { result.discontinuous() } else { result }
+    }
+
+    pub fn min(self, rhs: Interval) -> Interval {
+        binary(self, rhs, |a, b| Interval::new(a.lo.min(b.lo), a.hi.min(b.hi)))
+    }
+
+    pub fn max(self, rhs: Interval) -> Interval {
+        binary(self, rhs, |a, b| Interval::new(a.lo.max(b.lo), a.hi.max(b.hi)))
+    }
+
+    // mod(a, b) = a - b * floor(a / b), which jumps wherever floor(a / b) does.
+    pub fn modulo(self, rhs: Interval) -> Interval {
+        binary(self, rhs, |a, b| {
+            let quotient = a / b;
+            if quotient.is_empty() || quotient.discontinuous || quotient.lo.floor() != quotient.hi.floor() {
+                Interval::new(b.lo.min(0.0), b.hi.max(0.0)).inherit(quotient).discontinuous()
+            } else {
+                a - b * Interval::point(quotient.lo.floor())
+            }
+        })
+    }
+
+    fn widen(self) -> Interval {
+        Interval::rounded(self.lo, self.hi).inherit(self)
+    }
+}
+
+impl std::ops::Neg for Interval {
+    type Output = Interval;
+
+    fn neg(self) -> Interval {
+        Interval { lo: -self.hi, hi: -self.lo, ..self }
+    }
+}
+
+impl std::ops::Add for Interval {
+    type Output = Interval;
+
+    fn add(self, rhs: Interval) -> Interval {
+        binary(self, rhs, |a, b| Interval::rounded(a.lo + b.lo, a.hi + b.hi))
+    }
+}
+
+impl std::ops::Sub for Interval {
+    type Output = Interval;
+
+    fn sub(self, rhs: Interval) -> Interval {
+        binary(self, rhs, |a, b| Interval::rounded(a.lo - b.hi, a.hi - b.lo))
+    }
+}
+
+impl std::ops::Mul for Interval {
+    type Output = Interval;
+
+    fn mul(self, rhs: Interval) -> Interval {
+        binary(self, rhs, |a, b| {
+            // 0 * inf is taken as 0, since an unbounded end is never actually attained.
+            let product = |x: f64, y: f64| if x == 0.0 || y == 0.0 { 0.0 } else { x * y };
+            let corners = [product(a.lo, b.lo), product(a.lo, b.hi), product(a.hi, b.lo), product(a.hi, b.hi)];
+            Interval::rounded(corners.iter().copied().fold(f64::INFINITY, f64::min), corners.iter().copied().fold(f64::NEG_INFINITY, f64::max))
+        })
+    }
+}
+
+impl std::ops::Div for Interval {
+    type Output = Interval;
+
+    fn div(self, rhs: Interval) -> Interval {
+        binary(self, rhs, |a, b| {
+            if b.lo == 0.0 && b.hi == 0.0 {
+                Interval { partial: true, ..Interval::EMPTY }
+            } else if b.contains(0.0) {
+                Interval { partial: true, discontinuous: true, ..Interval::ENTIRE }
+            } else {
+                a * Interval::rounded(1.0 / b.hi, 1.0 / b.lo)
+            }
+        })
+    }
+}
+
+fn binary(lhs: Interval, rhs: Interval, op: impl Fn(Interval, Interval) -> Interval) -> Interval {
+    if lhs.is_empty() || rhs.is_empty() {
+        return Interval::EMPTY.inherit(lhs).inherit(rhs);
+    }
+    op(lhs, rhs).inherit(lhs).inherit(rhs)
+}
+
+impl Expr {
+    // Bounds the expression over the box given by the intervals in `env`.
+    pub fn eval_interval(&self, env: &Env<Interval>) -> Interval {
+        match self {
+            &Expr::Constant(value) => Interval::point(value),
+            Expr::ImaginaryUnit => Interval { partial: true, ..Interval::EMPTY },
+            Expr::Variable(name) => env.get(name).unwrap_or(Interval { partial: true, ..Interval::EMPTY }),
+            Expr::Neg(operand) => -operand.eval_interval(env),
+            Expr::Add(lhs, rhs) => lhs.eval_interval(env) + rhs.eval_interval(env),
+            Expr::Sub(lhs, rhs) => lhs.eval_interval(env) - rhs.eval_interval(env),
+            Expr::Mul(lhs, rhs) => lhs.eval_interval(env) * rhs.eval_interval(env),
+            Expr::Div(lhs, rhs) => lhs.eval_interval(env) / rhs.eval_interval(env),
+            Expr::Pow(lhs, rhs) => lhs.eval_interval(env).pow(rhs.eval_interval(env)),
+            Expr::Call(func, args) => {
+                let a = args[0].eval_interval(env);
+                let b = || args[1].eval_interval(env);
+                match func {
+                    Func::Sin => a.sin(),
+                    Func::Cos => a.cos(),
+                    Func::Tan => a.tan(),
+                    Func::Asin => a.restrict(-1.0, 1.0).increasing(f64::asin),
+                    Func::Acos => a.restrict(-1.0, 1.0).decreasing(f64::acos),
+                    Func::Atan => a.increasing(f64::atan),
+                    Func::Sinh => a.increasing(f64::sinh),
+                    Func::Cosh => a.cosh(),
+                    Func::Tanh => a.increasing(f64::tanh),
+                    Func::Exp => a.increasing(f64::exp),
+                    Func::Ln => a.restrict(0.0, f64::INFINITY).increasing(f64::ln),
+                    Func::Log => {
+                        let ln = |x: Interval| x.restrict(0.0, f64::INFINITY).increasing(f64::ln);
+                        ln(b()) / ln(a)
+                    }
+                    Func::Sqrt => a.restrict(0.0, f64::INFINITY).increasing(f64::sqrt),
+                    Func::Pow => a.pow(b()),
+                    Func::Abs => a.abs(),
+                    Func::Floor => a.floor(),
+                    Func::Ceil => a.ceil(),
+                    Func::Min => a.min(b()),
+                    Func::Max => a.max(b()),
+                    Func::Mod => a.modulo(b()),
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A small deterministic generator, so that failures reproduce.
+    struct Lcg(u64);
+
+    impl Lcg {
+        fn next(&mut self, lo: f64, hi: f64) -> f64 {
+            self.0 = self.0.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            lo + (hi - lo) * (self.0 >> 11) as f64 / (1u64 << 53) as f64
+        }
+    }
+
+    #[test]
+    fn bounds_contain_every_sampled_value() {
+        let mut rng = Lcg(7);
+        for source in [
+            "x^2 - y",
+            "x y - x / (y + 3)",
+            "sin(3x) cos(y) + tan(x)",
+            "exp(x) - ln(y + 2)",
+            "sqrt(abs(x y)) + x^-2",
+            "asin(x / 4) + acos(y / 4) + atan(x y)",
+            "sinh(x) - cosh(y) + tanh(x - y)",
+            "floor(x) + ceil(y) + mod(x, y)",
+            "min(x, y) max(x, y) + (x^2 + 1)^0.5",
+            "log(2, y + 4) + pow(abs(x), y)",
+        ] {
+            let expr = Expr::parse(source).unwrap();
+            for _ in 0..200 {
+                let (x0, y0) = (rng.next(-3.0, 3.0), rng.next(-3.0, 3.0));
+                let (x, y) = (Interval::new(x0, x0 + rng.next(0.0, 1.5)), Interval::new(y0, y0 + rng.next(0.0, 1.5)));
+                let bounds = expr.eval_interval(&Env::new().with("x", x).with("y", y));
+                for _ in 0..20 {
+                    let (px, py) = (rng.next(x.lo, x.hi), rng.next(y.lo, y.hi));
+                    let value = expr.eval_with(&Env::new().with("x", px).with("y", py));
+                    if value.is_finite() {
+                        assert!(bounds.contains(value), "{} at ({}, {}) is {}, outside of {:?} over {:?} x {:?}", source, px, py, value, bounds, x, y);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn arithmetic() {
+        let (a, b) = (Interval::new(1.0, 2.0), Interval::new(-3.0, 4.0));
+        let close = |i: Interval, lo: f64, hi: f64| assert!(i.contains(lo) && i.contains(hi) && i.lo > lo - 1e-12 && i.hi < hi + 1e-12, "{:?} is not about [{}, {}]", i, lo, hi);
+        close(-a, -2.0, -1.0);
+        close(a + b, -2.0, 6.0);
+        close(a - b, -3.0, 5.0);
+        close(a * b, -6.0, 8.0);
+        close(b / a, -3.0, 4.0);
+        close(a / Interval::new(2.0, 4.0), 0.25, 1.0);
+    }
+
+    #[test]
+    fn flags() {
+        let around_zero = Interval::new(-1.0, 1.0);
+        let env = Env::new().with("x", around_zero);
+        let ln = Expr::parse("ln(x)").unwrap().eval_interval(&env);
+        assert!(ln.partial && !ln.discontinuous && ln.hi >= 0.0);
+        let reciprocal = Expr::parse("1 / x").unwrap().eval_interval(&env);
+        assert!(reciprocal.discontinuous);
+        let tan = Expr::parse("tan(x + 1)").unwrap().eval_interval(&env);
+        assert!(tan.discontinuous);
+        let smooth = Expr::parse("x^2 + 1").unwrap().eval_interval(&env);
+        assert!(!smooth.partial && !smooth.discontinuous && smooth.contains(1.0) && smooth.contains(2.0));
+        assert!(Expr::parse("sqrt(x - 5)").unwrap().eval_interval(&env).is_empty());
+    }
+}
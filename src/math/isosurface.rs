@@ -0,0 +1,207 @@
+use std::collections::HashMap;
+
+// Kuhn decomposition of a grid cube into six tetrahedra around the diagonal from corner 0 to corner 7,
+// where bit 0 of a corner is its x offset, bit 1 its y offset and bit 2 its z offset. Neighbouring
+// cubes split their shared faces the same way, so the surface has no cracks.
+const TETRAHEDRA: [[usize; 4]; 6] = [[0, 1, 3, 7], [0, 2, 3, 7], [0, 2, 6, 7], [0, 4, 6, 7], [0, 4, 5, 7], [0, 1, 5, 7]];
+
+// Samples of a function on a regular grid of (counts[0] + 1) x (counts[1] + 1) x (counts[2] + 1)
+// points, stored with z varying fastest.
+pub struct SampleGrid {
+    pub min: [f64; 3],
+    pub step: [f64; 3],
+    pub counts: [usize; 3],
+    pub values: Vec<f64>,
+}
+
+impl SampleGrid {
+    // The grid points, as separate x, y and z arrays ready for `Bytecode::eval_slice`.
+    pub fn points(min: [f64; 3], step: [f64; 3], counts: [usize; 3]) -> [Vec<f64>; 3] {
+        let mut points = [Vec::new(), Vec::new(), Vec::new()];
+        for i in 0..=counts[0] {
+            for j in 0..=counts[1] {
+                for k in 0..=counts[2] {
+                    for (axis, index) in [i, j, k].into_iter().enumerate() {
+                        points[axis].push(min[axis] + index as f64 * step[axis]);
+                    }
+                }
+            }
+        }
+        points
+    }
+
+    // Samples the whole grid in one call, with `f(xs, ys, zs, values)` filling in the values.
+    pub fn sample(min: [f64; 3], step: [f64; 3], counts: [usize; 3], f: impl Fn(&[f64], &[f64], &[f64], &mut [f64])) -> SampleGrid {
+        let [xs, ys, zs] = SampleGrid::points(min, step, counts);
+        let mut values = vec![0.0; xs.len()];
+        f(&xs, &ys, &zs, &mut values);
+        SampleGrid { min, step, counts, values }
+    }
+
+    fn index(&self, i: usize, j: usize, k: usize) -> usize {
+        (i * (self.counts[1] + 1) + j) * (self.counts[2] + 1) + k
+    }
+
+    fn point(&self, i: usize, j: usize, k: usize) -> [f64; 3] {
+        [self.min[0] + i as f64 * self.step[0], self.min[1] + j as f64 * self.step[1], self.min[2] + k as f64 * self.step[2]]
+    }
+
+    // Triangulates the surface where the samples cross `level` with marching tetrahedra. Triangles are
+    // wound counterclockwise when seen from the side above the level. Vertices are shared between all
+    // the triangles that meet at a grid edge, so the mesh is closed wherever the samples are finite.
+    pub fn marching_tetrahedra(&self, level: f64) -> Mesh {
+        let mut mesh = Mesh { vertices: Vec::new(), triangles: Vec::new() };
+        let mut crossings: HashMap<(usize, usize), usize> = HashMap::new();
+        for i in 0..self.counts[0] {
+            for j in 0..self.counts[1] {
+                for k in 0..self.counts[2] {
+                    let corners: [(usize, usize, usize); 8] = std::array::from_fn(|c| (i + (c & 1), j + ((c >> 1) & 1), k + ((c >> 2) & 1)));
+                    let ids = corners.map(|(i, j, k)| self.index(i, j, k));
+                    let values = ids.map(|id| self.values[id] - level);
+                    if values.iter().any(|v| v.is_nan()) || values.iter().all(|&v| v < 0.0) || values.iter().all(|&v| v >= 0.0) {
+                        continue;
+                    }
+                    let points = corners.map(|(i, j, k)| self.point(i, j, k));
+
+                    for tetrahedron in TETRAHEDRA {
+                        let corner = |c: usize| Corner { id: ids[tetrahedron[c]], point: points[tetrahedron[c]], value: values[tetrahedron[c]] };
+                        tetrahedron_surface([corner(0), corner(1), corner(2), corner(3)], &mut crossings, &mut mesh);
+                    }
+                }
+            }
+        }
+        mesh
+    }
+}
+
+// An indexed triangle mesh.
+pub struct Mesh {
+    pub vertices: Vec<[f64; 3]>,
+    pub triangles: Vec<[usize; 3]>,
+}
+
+impl Mesh {
+    // Normals averaged from the triangles around each vertex, weighted by their area.
+    pub fn vertex_normals(&self) -> Vec<[f64; 3]> {
+        let mut normals = vec![[0.0; 3]; self.vertices.len()];
+        for &[a, b, c] in &self.triangles {
+            let normal = cross(sub(self.vertices[b], self.vertices[a]), sub(self.vertices[c], self.vertices[a]));
+            for v in [a, b, c] {
+                for axis in 0..3 {
+                    normals[v][axis] += normal[axis];
+                }
+            }
+        }
+        normals
+            .into_iter()
+            .map(|n| {
+                let length = dot(n, n).sqrt();
+                if length > 0.0 { n.map(|x| x / length) } else { n }
+            })
+            .collect()
+    }
+}
+
+struct Corner {
+    id: usize,
+    point: [f64; 3],
+    value: f64,
+}
+
+fn tetrahedron_surface(corners: [Corner; 4], crossings: &mut HashMap<(usize, usize), usize>, mesh: &mut Mesh) {
+    let (negative, positive): (Vec<usize>, Vec<usize>) = (0..4).partition(|&c| corners[c].value < 0.0);
+    // Keyed by the grid edge, so every tetrahedron around the edge gets the same vertex.
+    let mut crossing = |a: usize, b: usize| {
+        let (a, b) = (&corners[a], &corners[b]);
+        *crossings.entry((a.id.min(b.id), a.id.max(b.id))).or_insert_with(|| {
+            let t = a.value / (a.value - b.value);
+            mesh.vertices.push(std::array::from_fn(|axis| a.point[axis] + t * (b.point[axis] - a.point[axis])));
+            mesh.vertices.len() - 1
+        })
+    };
+
+    let polygon: Vec<usize> = match (negative.as_slice(), positive.as_slice()) {
+        ([a], [b, c, d]) | ([b, c, d], [a]) => vec![crossing(*a, *b), crossing(*a, *c), crossing(*a, *d)],
+        // The crossings of the four edges between the two pairs, in order around the quad.
+        ([a, b], [c, d]) => vec![crossing(*a, *c), crossing(*a, *d), crossing(*b, *d), crossing(*b, *c)],
+        _ => return,
+    };
+
+    // Winds the polygon so that its normal points from the negative to the positive corners.
+    let centroid = |group: &[usize]| -> [f64; 3] { std::array::from_fn(|axis| group.iter().map(|&c| corners[c].point[axis]).sum::<f64>() / group.len() as f64) };
+    let direction = sub(centroid(&positive), centroid(&negative));
+    let point = |v: usize| mesh.vertices[polygon[v]];
+    let normal = cross(sub(point(1), point(0)), sub(point(2), point(0)));
+    let flip = dot(normal, direction) < 0.0;
+
+    for k in 1..polygon.len() - 1 {
+        mesh.triangles.push(if flip { [polygon[0], polygon[k + 1], polygon[k]] } else { [polygon[0], polygon[k], polygon[k + 1]] });
+    }
+}
+
+fn sub(a: [f64; 3], b: [f64; 3]) -> [f64; 3] {
+    [a[0] - b[0], a[1] - b[1], a[2] - b[2]]
+}
+
+fn cross(a: [f64; 3], b: [f64; 3]) -> [f64; 3] {
+    [a[1] * b[2] - a[2] * b[1], a[2] * b[0] - a[0] * b[2], a[0] * b[1] - a[1] * b[0]]
+}
+
+fn dot(a: [f64; 3], b: [f64; 3]) -> f64 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The unit sphere as the level set of x^2 + y^2 + z^2 on a coarse grid, whose gradient points out.
+    fn sphere() -> Mesh {
+        let grid = SampleGrid::sample([-1.3; 3], [0.2; 3], [13; 3], |xs, ys, zs, values| {
+            for (n, value) in values.iter_mut().enumerate() {
+                *value = xs[n] * xs[n] + ys[n] * ys[n] + zs[n] * zs[n];
+            }
+        });
+        grid.marching_tetrahedra(1.0)
+    }
+
+    #[test]
+    fn sphere_is_closed() {
+        let mesh = sphere();
+        assert!(!mesh.triangles.is_empty());
+        let mut edges: HashMap<(usize, usize), usize> = HashMap::new();
+        for &[a, b, c] in &mesh.triangles {
+            for (from, to) in [(a, b), (b, c), (c, a)] {
+                *edges.entry((from, to)).or_default() += 1;
+            }
+        }
+        // Every edge is shared by two triangles, which run along it in opposite directions.
+        for (&(from, to), &count) in &edges {
+            assert_eq!(count, 1, "edge {} -> {} is used {} times", from, to, count);
+            assert_eq!(edges.get(&(to, from)), Some(&1), "edge {} -> {} has no opposite", from, to);
+        }
+    }
+
+    #[test]
+    fn winding_follows_the_gradient() {
+        let mesh = sphere();
+        for v in &mesh.vertices {
+            assert!((dot(*v, *v).sqrt() - 1.0).abs() < 0.05);
+        }
+        for &[a, b, c] in &mesh.triangles {
+            let normal = cross(sub(mesh.vertices[b], mesh.vertices[a]), sub(mesh.vertices[c], mesh.vertices[a]));
+            assert!(dot(normal, mesh.vertices[a]) >= 0.0);
+        }
+        for (normal, v) in mesh.vertex_normals().into_iter().zip(&mesh.vertices) {
+            assert!(dot(normal, *v) / dot(*v, *v).sqrt() > 0.9);
+        }
+    }
+
+    #[test]
+    fn skips_cells_with_nan_samples() {
+        let grid = SampleGrid { min: [0.0; 3], step: [1.0; 3], counts: [1; 3], values: vec![-1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, f64::NAN] };
+        assert!(grid.marching_tetrahedra(0.0).triangles.is_empty());
+        let grid = SampleGrid { values: vec![-1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0], ..grid };
+        assert_eq!(grid.marching_tetrahedra(0.0).triangles.len(), 6);
+    }
+}
@@ -1,2 +1,15 @@
+pub mod bytecode;
 pub mod complex;
-pub mod expr;
\ No newline at end of file
+pub mod derivative;
+pub mod expr;
+pub mod flow;
+pub mod func;
+pub mod glsl;
+pub mod interval;
+pub mod isosurface;
+pub mod nd;
+pub mod ode;
+pub mod parser;
+pub mod polytope;
+pub mod simplify;
+pub mod wythoff;
\ No newline at end of file
@@ -0,0 +1,337 @@
+use std::ops::{Add, Index, IndexMut, Mul, Neg, Sub};
+
+// Vectors and matrices whose size is only known at runtime, for geometry in any number of dimensions.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Vector {
+    data: Vec<f64>,
+}
+
+impl Vector {
+    pub fn zeros(n: usize) -> Vector {
+        Vector { data: vec![0.0; n] }
+    }
+
+    // The unit vector along `axis`.
+    pub fn axis(n: usize, axis: usize) -> Vector {
+        let mut vector = Vector::zeros(n);
+        vector[axis] = 1.0;
+        vector
+    }
+
+    pub fn dimensions(&self) -> usize {
+        self.data.len()
+    }
+
+    pub fn as_slice(&self) -> &[f64] {
+        &self.data
+    }
+
+    pub fn dot(&self, rhs: &Vector) -> f64 {
+        assert_eq!(self.dimensions(), rhs.dimensions(), "dimension mismatch");
+        self.data.iter().zip(&rhs.data).map(|(a, b)| a * b).sum()
+    }
+
+    pub fn norm(&self) -> f64 {
+        self.dot(self).sqrt()
+    }
+
+    pub fn normalize(&self) -> Vector {
+        self * (1.0 / self.norm())
+    }
+
+    // Appends a 1, for use with the homogeneous matrices from `Matrix::perspective`.
+    pub fn homogeneous(&self) -> Vector {
+        let mut data = self.data.clone();
+        data.push(1.0);
+        Vector { data }
+    }
+
+    // Divides by the last coordinate and drops it.
+    pub fn dehomogenize(&self) -> Vector {
+        let (&w, rest) = self.data.split_last().expect("empty vector");
+        Vector { data: rest.iter().map(|x| x / w).collect() }
+    }
+}
+
+impl From<Vec<f64>> for Vector {
+    fn from(data: Vec<f64>) -> Vector {
+        Vector { data }
+    }
+}
+
+impl From<&[f64]> for Vector {
+    fn from(data: &[f64]) -> Vector {
+        Vector { data: data.to_vec() }
+    }
+}
+
+impl Index<usize> for Vector {
+    type Output = f64;
+
+    fn index(&self, i: usize) -> &f64 {
+        &self.data[i]
+    }
+}
+
+impl IndexMut<usize> for Vector {
+    fn index_mut(&mut self, i: usize) -> &mut f64 {
+        &mut self.data[i]
+    }
+}
+
+impl Neg for &Vector {
+    type Output = Vector;
+
+    fn neg(self) -> Vector {
+        Vector { data: self.data.iter().map(|x| -x).collect() }
+    }
+}
+
+impl Add for &Vector {
+    type Output = Vector;
+
+    fn add(self, rhs: &Vector) -> Vector {
+        assert_eq!(self.dimensions(), rhs.dimensions(), "dimension mismatch");
+        Vector { data: self.data.iter().zip(&rhs.data).map(|(a, b)| a + b).collect() }
+    }
+}
+
+impl Sub for &Vector {
+    type Output = Vector;
+
+    fn sub(self, rhs: &Vector) -> Vector {
+        assert_eq!(self.dimensions(), rhs.dimensions(), "dimension mismatch");
+        Vector { data: self.data.iter().zip(&rhs.data).map(|(a, b)| a - b).collect() }
+    }
+}
+
+impl Mul<f64> for &Vector {
+    type Output = Vector;
+
+    fn mul(self, rhs: f64) -> Vector {
+        Vector { data: self.data.iter().map(|x| x * rhs).collect() }
+    }
+}
+
+// Orthonormalizes `vectors` with modified Gram-Schmidt. Vectors that are (nearly) linear
+// combinations of the ones before them are dropped, so the result spans the same space.
+pub fn gram_schmidt(vectors: &[Vector]) -> Vec<Vector> {
+    let mut basis: Vec<Vector> = Vec::with_capacity(vectors.len());
+    for vector in vectors {
+        let mut v = vector.clone();
+        for b in &basis {
+            v = &v - &(b * v.dot(b));
+        }
+        let norm = v.norm();
+        if norm > 1e-9 * vector.norm().max(1.0) {
+            basis.push(&v * (1.0 / norm));
+        }
+    }
+    basis
+}
+
+// Row major.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Matrix {
+    rows: usize,
+    cols: usize,
+    data: Vec<f64>,
+}
+
+impl Matrix {
+    pub fn zeros(rows: usize, cols: usize) -> Matrix {
+        Matrix { rows, cols, data: vec![0.0; rows * cols] }
+    }
+
+    pub fn identity(n: usize) -> Matrix {
+        let mut matrix = Matrix::zeros(n, n);
+        for i in 0..n {
+            matrix[(i, i)] = 1.0;
+        }
+        matrix
+    }
+
+    pub fn from_rows(rows: &[Vector]) -> Matrix {
+        let cols = rows.first().map_or(0, Vector::dimensions);
+        assert!(rows.iter().all(|row| row.dimensions() == cols), "rows have different lengths");
+        Matrix { rows: rows.len(), cols, data: rows.iter().flat_map(|row| row.data.iter().copied()).collect() }
+    }
+
+    // The rotation by `angle` in the plane spanned by the axes `a` and `b`, turning `a` towards `b`.
+    pub fn givens(n: usize, a: usize, b: usize, angle: f64) -> Matrix {
+        let mut matrix = Matrix::identity(n);
+        let (sin, cos) = angle.sin_cos();
+        matrix[(a, a)] = cos;
+        matrix[(a, b)] = -sin;
+        matrix[(b, a)] = sin;
+        matrix[(b, b)] = cos;
+        matrix
+    }
+
+    // Keeps the first `m` of `n` coordinates.
+    pub fn orthographic(n: usize, m: usize) -> Matrix {
+        assert!(m <= n, "can't project to more dimensions");
+        let mut matrix = Matrix::zeros(m, n);
+        for i in 0..m {
+            matrix[(i, i)] = 1.0;
+        }
+        matrix
+    }
+
+    // Perspective projection from n to n - 1 dimensions with the eye at `eye_distance` on the last
+    // axis, looking towards the origin. Acts on homogeneous coordinates, so the result is n x (n + 1)
+    // and has to be dehomogenized: a point at depth d is scaled by eye_distance / (eye_distance - d).
+    pub fn perspective(n: usize, eye_distance: f64) -> Matrix {
+        let mut matrix = Matrix::zeros(n, n + 1);
+        for i in 0..n - 1 {
+            matrix[(i, i)] = 1.0;
+        }
+        matrix[(n - 1, n - 1)] = -1.0 / eye_distance;
+        matrix[(n - 1, n)] = 1.0;
+        matrix
+    }
+
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    pub fn cols(&self) -> usize {
+        self.cols
+    }
+
+    pub fn row(&self, i: usize) -> &[f64] {
+        &self.data[i * self.cols..(i + 1) * self.cols]
+    }
+
+    pub fn transpose(&self) -> Matrix {
+        let mut matrix = Matrix::zeros(self.cols, self.rows);
+        for i in 0..self.rows {
+            for j in 0..self.cols {
+                matrix[(j, i)] = self[(i, j)];
+            }
+        }
+        matrix
+    }
+
+    // Replaces the rows with an orthonormal basis for them, undoing the drift from repeated rotations.
+    pub fn orthonormalize(&mut self) {
+        let rows: Vec<Vector> = (0..self.rows).map(|i| Vector::from(self.row(i))).collect();
+        let basis = gram_schmidt(&rows);
+        assert_eq!(basis.len(), self.rows, "rows are linearly dependent");
+        *self = Matrix::from_rows(&basis);
+    }
+}
+
+impl Index<(usize, usize)> for Matrix {
+    type Output = f64;
+
+    fn index(&self, (i, j): (usize, usize)) -> &f64 {
+        assert!(j < self.cols, "column {} out of range for {} columns", j, self.cols);
+        &self.data[i * self.cols + j]
+    }
+}
+
+impl IndexMut<(usize, usize)> for Matrix {
+    fn index_mut(&mut self, (i, j): (usize, usize)) -> &mut f64 {
+        assert!(j < self.cols, "column {} out of range for {} columns", j, self.cols);
+        &mut self.data[i * self.cols + j]
+    }
+}
+
+impl Mul for &Matrix {
+    type Output = Matrix;
+
+    fn mul(self, rhs: &Matrix) -> Matrix {
+        assert_eq!(self.cols, rhs.rows, "dimension mismatch");
+        let mut matrix = Matrix::zeros(self.rows, rhs.cols);
+        for i in 0..self.rows {
+            for k in 0..self.cols {
+                let a = self[(i, k)];
+                for j in 0..rhs.cols {
+                    matrix[(i, j)] += a * rhs[(k, j)];
+                }
+            }
+        }
+        matrix
+    }
+}
+
+impl Mul<&Vector> for &Matrix {
+    type Output = Vector;
+
+    fn mul(self, rhs: &Vector) -> Vector {
+        assert_eq!(self.cols, rhs.dimensions(), "dimension mismatch");
+        Vector { data: (0..self.rows).map(|i| self.row(i).iter().zip(&rhs.data).map(|(a, b)| a * b).sum()).collect() }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_identity(matrix: &Matrix) {
+        for i in 0..matrix.rows() {
+            for j in 0..matrix.cols() {
+                let expected = if i == j { 1.0 } else { 0.0 };
+                assert!((matrix[(i, j)] - expected).abs() < 1e-12, "entry ({}, {}) is {}", i, j, matrix[(i, j)]);
+            }
+        }
+    }
+
+    #[test]
+    fn givens_rotations_are_orthogonal() {
+        let mut rotation = Matrix::identity(5);
+        for (k, (a, b)) in [(0, 3), (1, 4), (2, 0), (3, 1), (4, 2)].into_iter().cycle().take(200).enumerate() {
+            rotation = &Matrix::givens(5, a, b, 0.1 + k as f64 * 0.37) * &rotation;
+        }
+        assert_identity(&(&rotation * &rotation.transpose()));
+        rotation.orthonormalize();
+        assert_identity(&(&rotation.transpose() * &rotation));
+    }
+
+    #[test]
+    fn givens_turns_a_towards_b() {
+        let rotation = Matrix::givens(4, 1, 3, std::f64::consts::FRAC_PI_2);
+        let turned = &rotation * &Vector::axis(4, 1);
+        assert!((&turned - &Vector::axis(4, 3)).norm() < 1e-12, "{:?}", turned);
+        assert_eq!(&rotation * &Vector::axis(4, 0), Vector::axis(4, 0));
+    }
+
+    #[test]
+    fn gram_schmidt_drops_dependent_vectors() {
+        let vectors = [Vector::from(vec![1.0, 1.0, 0.0]), Vector::from(vec![2.0, 2.0, 0.0]), Vector::from(vec![1.0, 0.0, 1.0])];
+        let basis = gram_schmidt(&vectors);
+        assert_eq!(basis.len(), 2);
+        assert_identity(&(&Matrix::from_rows(&basis) * &Matrix::from_rows(&basis).transpose()));
+    }
+
+    #[test]
+    fn products() {
+        let a = Matrix::from_rows(&[Vector::from(vec![1.0, 2.0, 3.0]), Vector::from(vec![4.0, 5.0, 6.0])]);
+        let b = a.transpose();
+        assert_eq!(&a * &b, Matrix::from_rows(&[Vector::from(vec![14.0, 32.0]), Vector::from(vec![32.0, 77.0])]));
+        assert_eq!(&a * &Vector::from(vec![1.0, 0.0, -1.0]), Vector::from(vec![-2.0, -2.0]));
+    }
+
+    #[test]
+    fn orthographic_keeps_the_first_coordinates() {
+        let point = Vector::from(vec![1.0, 2.0, 3.0, 4.0, 5.0]);
+        assert_eq!(&Matrix::orthographic(5, 3) * &point, Vector::from(vec![1.0, 2.0, 3.0]));
+    }
+
+    #[test]
+    fn perspective_scales_by_depth() {
+        // At depth 2 with the eye at 8, the point is scaled by 8 / (8 - 2).
+        let point = Vector::from(vec![3.0, -6.0, 1.5, 2.0]);
+        let projected = (&Matrix::perspective(4, 8.0) * &point.homogeneous()).dehomogenize();
+        let expected = Vector::from(vec![4.0, -8.0, 2.0]);
+        assert!((&projected - &expected).norm() < 1e-12, "{:?}", projected);
+    }
+
+    #[test]
+    #[should_panic(expected = "column 3 out of range")]
+    fn indices_past_the_last_column_panic() {
+        let matrix = Matrix::zeros(3, 3);
+        let _ = matrix[(0, 3)];
+    }
+}
@@ -0,0 +1,227 @@
+use super::{bytecode::Bytecode, expr::Expr};
+
+// A system of first order equations y_i' = f_i(t, y), with the right-hand sides given as expressions in
+// t, the variables y_i and any named constants.
+pub struct OdeSystem {
+    variables: Vec<String>,
+    rhs: Vec<Bytecode>,
+    constants: Vec<f64>,
+}
+
+impl OdeSystem {
+    pub fn new(equations: &[(String, Expr)], constants: &[(String, f64)]) -> Result<OdeSystem, String> {
+        let variables: Vec<String> = equations.iter().map(|(name, _)| name.clone()).collect();
+        if let Some(name) = variables.iter().find(|name| *name == "t" || constants.iter().any(|(constant, _)| constant == *name)) {
+            return Err(format!("'{}' can't be both a variable and {}", name, if name == "t" { "the time" } else { "a constant" }));
+        }
+
+        let inputs: Vec<&str> = ["t"].into_iter().chain(variables.iter().map(String::as_str)).chain(constants.iter().map(|(name, _)| name.as_str())).collect();
+        let rhs = equations.iter().map(|(_, expr)| expr.compile(&inputs)).collect::<Result<_, _>>()?;
+        Ok(OdeSystem {
+            variables,
+            rhs,
+            constants: constants.iter().map(|&(_, value)| value).collect(),
+        })
+    }
+
+    pub fn variables(&self) -> &[String] {
+        &self.variables
+    }
+
+    pub fn eval(&self, t: f64, y: &[f64]) -> Vec<f64> {
+        let mut args = Vec::with_capacity(1 + y.len() + self.constants.len());
+        args.push(t);
+        args.extend_from_slice(y);
+        args.extend_from_slice(&self.constants);
+        self.rhs.iter().map(|f| f.eval(&args)).collect()
+    }
+}
+
+// The states of a solution at increasing times.
+pub struct Trajectory {
+    pub times: Vec<f64>,
+    pub states: Vec<Vec<f64>>,
+}
+
+// One step of the classic fourth order Runge-Kutta method.
+pub fn rk4_step(f: impl Fn(f64, &[f64]) -> Vec<f64>, t: f64, y: &[f64], h: f64) -> Vec<f64> {
+    let offset = |k: &[f64], scale: f64| -> Vec<f64> { y.iter().zip(k).map(|(y, k)| y + k * scale).collect() };
+    let k1 = f(t, y);
+    let k2 = f(t + h / 2.0, &offset(&k1, h / 2.0));
+    let k3 = f(t + h / 2.0, &offset(&k2, h / 2.0));
+    let k4 = f(t + h, &offset(&k3, h));
+    (0..y.len()).map(|i| y[i] + h / 6.0 * (k1[i] + 2.0 * k2[i] + 2.0 * k3[i] + k4[i])).collect()
+}
+
+// Integrates from t0 to t1 with RK4 steps of at most `h`, the last one shortened to land on t1.
+pub fn rk4(f: impl Fn(f64, &[f64]) -> Vec<f64>, y0: &[f64], t0: f64, t1: f64, h: f64) -> Result<Trajectory, String> {
+    if h.is_nan() || h <= 0.0 {
+        return Err(format!("the step has to be positive, got {}", h));
+    }
+    let steps = ((t1 - t0) / h).ceil();
+    if steps > MAX_STEPS as f64 {
+        return Err(format!("{} steps of {} are too many, the limit is {}", steps, h, MAX_STEPS));
+    }
+
+    let mut trajectory = Trajectory { times: vec![t0], states: vec![y0.to_vec()] };
+    let (mut t, mut y) = (t0, y0.to_vec());
+    for step in 1..=steps as usize {
+        let next = (t0 + step as f64 * h).min(t1);
+        y = rk4_step(&f, t, &y, next - t);
+        t = next;
+        trajectory.times.push(t);
+        trajectory.states.push(y.clone());
+    }
+    Ok(trajectory)
+}
+
+const MAX_STEPS: usize = 1_000_000;
+
+// The Dormand-Prince 5(4) tableau.
+const C: [f64; 7] = [0.0, 1.0 / 5.0, 3.0 / 10.0, 4.0 / 5.0, 8.0 / 9.0, 1.0, 1.0];
+const A: [[f64; 6]; 7] = [
+    [0.0; 6],
+    [1.0 / 5.0, 0.0, 0.0, 0.0, 0.0, 0.0],
+    [3.0 / 40.0, 9.0 / 40.0, 0.0, 0.0, 0.0, 0.0],
+    [44.0 / 45.0, -56.0 / 15.0, 32.0 / 9.0, 0.0, 0.0, 0.0],
+    [19372.0 / 6561.0, -25360.0 / 2187.0, 64448.0 / 6561.0, -212.0 / 729.0, 0.0, 0.0],
+    [9017.0 / 3168.0, -355.0 / 33.0, 46732.0 / 5247.0, 49.0 / 176.0, -5103.0 / 18656.0, 0.0],
+    [35.0 / 384.0, 0.0, 500.0 / 1113.0, 125.0 / 192.0, -2187.0 / 6784.0, 11.0 / 84.0],
+];
+// Weights of the fifth order solution, which are also the last row of A, minus those of the embedded
+// fourth order one. Their difference estimates the error of a step.
+const ERROR: [f64; 7] = [71.0 / 57600.0, 0.0, -71.0 / 16695.0, 71.0 / 1920.0, -17253.0 / 339200.0, 22.0 / 525.0, -1.0 / 40.0];
+
+// Integrates from t0 to t1 with the adaptive Dormand-Prince method, choosing each step so that its
+// estimated error stays below `tolerance`, both absolute and relative to the size of the state. Steps
+// are also kept below `max_step`, so that curves through the points stay smooth.
+pub fn dormand_prince(f: impl Fn(f64, &[f64]) -> Vec<f64>, y0: &[f64], t0: f64, t1: f64, tolerance: f64, max_step: f64) -> Result<Trajectory, String> {
+    if tolerance.is_nan() || tolerance <= 0.0 {
+        return Err(format!("the tolerance has to be positive, got {}", tolerance));
+    }
+
+    let n = y0.len();
+    let mut trajectory = Trajectory { times: vec![t0], states: vec![y0.to_vec()] };
+    let (mut t, mut y) = (t0, y0.to_vec());
+    let mut h = ((t1 - t0) / 100.0).min(max_step);
+    // The last stage of an accepted step is evaluated at its end, so it is the first stage of the next.
+    let mut first = f(t, &y);
+
+    while t < t1 {
+        if trajectory.times.len() > MAX_STEPS {
+            return Err(format!("gave up at t = {} after {} steps", t, MAX_STEPS));
+        }
+        // The last step lands exactly on t1, instead of leaving a sliver that rounding made.
+        let last = h >= t1 - t;
+        if last {
+            h = t1 - t;
+        }
+        if h <= 1e-12 * t.abs().max(1.0) {
+            return Err(format!("the step size vanished at t = {}, the solution may blow up there", t));
+        }
+
+        let mut k = Vec::with_capacity(7);
+        k.push(first.clone());
+        let mut stage = y.clone();
+        for s in 1..7 {
+            stage = (0..n).map(|i| y[i] + h * (0..s).map(|j| A[s][j] * k[j][i]).sum::<f64>()).collect();
+            k.push(f(t + C[s] * h, &stage));
+        }
+        // The seventh stage is evaluated at the fifth order solution itself.
+        let next = stage;
+
+        let error = ((0..n)
+            .map(|i| {
+                let estimate = h * (0..7).map(|s| ERROR[s] * k[s][i]).sum::<f64>();
+                let scale = tolerance * (1.0 + y[i].abs().max(next[i].abs()));
+                (estimate / scale).powi(2)
+            })
+            .sum::<f64>()
+            / n.max(1) as f64)
+            .sqrt();
+
+        if error <= 1.0 {
+            t = if last { t1 } else { t + h };
+            y = next;
+            first = k.swap_remove(6);
+            trajectory.times.push(t);
+            trajectory.states.push(y.clone());
+        }
+        // A non-finite error halves the step, a finite one scales it by the usual safety factor.
+        let factor = if error.is_finite() { (0.9 * error.powf(-0.2)).clamp(0.2, 5.0) } else { 0.5 };
+        h = (h * factor).min(max_step);
+    }
+    Ok(trajectory)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn growth(_: f64, y: &[f64]) -> Vec<f64> {
+        vec![y[0]]
+    }
+
+    fn final_error(trajectory: &Trajectory) -> f64 {
+        let t = *trajectory.times.last().unwrap();
+        (trajectory.states.last().unwrap()[0] - t.exp()).abs()
+    }
+
+    #[test]
+    fn rk4_is_fourth_order() {
+        let coarse = final_error(&rk4(growth, &[1.0], 0.0, 1.0, 0.1).unwrap());
+        let fine = final_error(&rk4(growth, &[1.0], 0.0, 1.0, 0.05).unwrap());
+        let ratio = coarse / fine;
+        assert!((ratio - 16.0).abs() < 1.0, "halving the step divided the error by {}", ratio);
+    }
+
+    #[test]
+    fn rk4_lands_on_the_end() {
+        let trajectory = rk4(growth, &[1.0], 0.0, 1.0, 0.3).unwrap();
+        assert_eq!(trajectory.times.len(), 5);
+        assert_eq!(*trajectory.times.last().unwrap(), 1.0);
+        assert!(rk4(growth, &[1.0], 0.0, 1.0, 0.0).is_err());
+        assert!(rk4(growth, &[1.0], 0.0, 1e7, 1.0).is_err());
+    }
+
+    #[test]
+    fn dormand_prince_error_follows_the_tolerance() {
+        let mut previous = (f64::INFINITY, 0);
+        for tolerance in [1e-4, 1e-6, 1e-8, 1e-10] {
+            let trajectory = dormand_prince(growth, &[1.0], 0.0, 2.0, tolerance, 1.0).unwrap();
+            assert_eq!(*trajectory.times.last().unwrap(), 2.0);
+            let error = final_error(&trajectory);
+            assert!(error < 100.0 * tolerance, "error {} at tolerance {}", error, tolerance);
+            assert!(error < previous.0 && trajectory.times.len() > previous.1);
+            previous = (error, trajectory.times.len());
+        }
+    }
+
+    #[test]
+    fn dormand_prince_respects_the_largest_step() {
+        let trajectory = dormand_prince(|_, _| vec![1.0], &[0.0], 0.0, 1.0, 1e-6, 0.1).unwrap();
+        assert!(trajectory.times.windows(2).all(|w| w[1] - w[0] <= 0.1 + 1e-12));
+        assert!((trajectory.states.last().unwrap()[0] - 1.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn dormand_prince_stops_at_a_blow_up() {
+        // y' = y^2 with y(0) = 1 has the solution 1 / (1 - t).
+        let error = dormand_prince(|_, y| vec![y[0] * y[0]], &[1.0], 0.0, 2.0, 1e-6, 0.1).err().unwrap();
+        let t: f64 = error.strip_prefix("the step size vanished at t = ").and_then(|rest| rest.split(',').next()).unwrap().parse().unwrap();
+        assert!((t - 1.0).abs() < 1e-3, "{}", error);
+    }
+
+    #[test]
+    fn systems() {
+        let equations = [("x".to_string(), Expr::parse("a y").unwrap()), ("y".to_string(), Expr::parse("-x + t").unwrap())];
+        let system = OdeSystem::new(&equations, &[("a".to_string(), 2.0)]).unwrap();
+        assert_eq!(system.variables(), ["x", "y"]);
+        assert_eq!(system.eval(3.0, &[1.0, 5.0]), vec![10.0, 2.0]);
+
+        let time = [("t".to_string(), Expr::parse("1").unwrap())];
+        assert_eq!(OdeSystem::new(&time, &[]).err().unwrap(), "'t' can't be both a variable and the time");
+        assert_eq!(OdeSystem::new(&equations, &[("x".to_string(), 1.0)]).err().unwrap(), "'x' can't be both a variable and a constant");
+        assert!(OdeSystem::new(&[("x".to_string(), Expr::parse("z").unwrap())], &[]).is_err());
+    }
+}
@@ -0,0 +1,356 @@
+use std::ops::Range;
+
+use super::{expr::Expr, func::Func};
+
+#[derive(Clone, Debug)]
+pub struct ParseError {
+    pub message: String,
+    pub span: Range<usize>,
+}
+
+impl ParseError {
+    fn new(message: impl Into<String>, span: Range<usize>) -> ParseError {
+        ParseError { message: message.into(), span }
+    }
+
+    // Renders the source with a line of carets under the offending bytes.
+    pub fn underline(&self, source: &str) -> String {
+        let start = source[..self.span.start].chars().count();
+        let len = source[self.span.clone()].chars().count().max(1);
+        format!("{}\n{}{}", source, " ".repeat(start), "^".repeat(len))
+    }
+}
+
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} at {}..{}", self.message, self.span.start, self.span.end)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum TokenKind {
+    Number(f64),
+    Ident,
+    Plus,
+    Minus,
+    Star,
+    Slash,
+    Caret,
+    LParen,
+    RParen,
+    Comma,
+    End,
+}
+
+#[derive(Clone, Debug)]
+struct Token {
+    kind: TokenKind,
+    span: Range<usize>,
+}
+
+fn tokenize(source: &str) -> Result<Vec<Token>, ParseError> {
+    let bytes = source.as_bytes();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+
+    while i < bytes.len() {
+        let c = bytes[i];
+        let start = i;
+
+        if c.is_ascii_whitespace() {
+            i += 1;
+            continue;
+        }
+
+        let kind = if c.is_ascii_digit() || c == b'.' {
+            while i < bytes.len() && (bytes[i].is_ascii_digit() || bytes[i] == b'.') {
+                i += 1;
+            }
+            // Only treat 'e' as an exponent when digits follow, so that "2e" stays 2 * e.
+            if i < bytes.len() && (bytes[i] == b'e' || bytes[i] == b'E') {
+                let mut j = i + 1;
+                if j < bytes.len() && (bytes[j] == b'+' || bytes[j] == b'-') {
+                    j += 1;
+                }
+                if j < bytes.len() && bytes[j].is_ascii_digit() {
+                    i = j;
+                    while i < bytes.len() && bytes[i].is_ascii_digit() {
+                        i += 1;
+                    }
+                }
+            }
+            match source[start..i].parse::<f64>() {
+                Ok(value) => TokenKind::Number(value),
+                Err(_) => return Err(ParseError::new(format!("invalid number '{}'", &source[start..i]), start..i)),
+            }
+        } else if c.is_ascii_alphabetic() || c == b'_' {
+            while i < bytes.len() && (bytes[i].is_ascii_alphanumeric() || bytes[i] == b'_') {
+                i += 1;
+            }
+            TokenKind::Ident
+        } else {
+            i += 1;
+            match c {
+                b'+' => TokenKind::Plus,
+                b'-' => TokenKind::Minus,
+                b'*' => TokenKind::Star,
+                b'/' => TokenKind::Slash,
+                b'^' => TokenKind::Caret,
+                b'(' => TokenKind::LParen,
+                b')' => TokenKind::RParen,
+                b',' => TokenKind::Comma,
+                _ => {
+                    let len = source[start..].chars().next().map_or(1, char::len_utf8);
+                    return Err(ParseError::new(format!("unexpected character '{}'", &source[start..start + len]), start..start + len));
+                }
+            }
+        };
+
+        tokens.push(Token { kind, span: start..i });
+    }
+
+    tokens.push(Token { kind: TokenKind::End, span: source.len()..source.len() });
+    Ok(tokens)
+}
+
+struct Parser<'a> {
+    source: &'a str,
+    tokens: Vec<Token>,
+    position: usize,
+    // Whether "i" is the imaginary unit. Real plots reject it, instead of plotting nothing.
+    complex: bool,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> &Token {
+        &self.tokens[self.position]
+    }
+
+    fn next(&mut self) -> Token {
+        let token = self.tokens[self.position].clone();
+        if token.kind != TokenKind::End {
+            self.position += 1;
+        }
+        token
+    }
+
+    fn unexpected(&self, token: &Token, expected: &str) -> ParseError {
+        match token.kind {
+            TokenKind::End => ParseError::new(format!("expected {}, found end of input", expected), token.span.clone()),
+            _ => ParseError::new(format!("expected {}, found '{}'", expected, &self.source[token.span.clone()]), token.span.clone()),
+        }
+    }
+
+    // sum := product (('+' | '-') product)*
+    fn sum(&mut self) -> Result<Expr, ParseError> {
+        let mut lhs = self.product()?;
+        loop {
+            match self.peek().kind {
+                TokenKind::Plus => {
+                    self.next();
+                    lhs = Expr::add(lhs, self.product()?);
+                }
+                TokenKind::Minus => {
+                    self.next();
+                    lhs = Expr::sub(lhs, self.product()?);
+                }
+                _ => return Ok(lhs),
+            }
+        }
+    }
+
+    // product := unary (('*' | '/') unary | power)*
+    // A power directly following an operand is an implicit multiplication, as in "2x" or "3(x + 1)".
+    fn product(&mut self) -> Result<Expr, ParseError> {
+        let mut lhs = self.unary()?;
+        loop {
+            match self.peek().kind {
+                TokenKind::Star => {
+                    self.next();
+                    lhs = Expr::mul(lhs, self.unary()?);
+                }
+                TokenKind::Slash => {
+                    self.next();
+                    lhs = Expr::div(lhs, self.unary()?);
+                }
+                TokenKind::Ident | TokenKind::LParen => {
+                    lhs = Expr::mul(lhs, self.power()?);
+                }
+                _ => return Ok(lhs),
+            }
+        }
+    }
+
+    // unary := ('-' | '+') unary | power
+    fn unary(&mut self) -> Result<Expr, ParseError> {
+        match self.peek().kind {
+            TokenKind::Minus => {
+                self.next();
+                Ok(-self.unary()?)
+            }
+            TokenKind::Plus => {
+                self.next();
+                self.unary()
+            }
+            _ => self.power(),
+        }
+    }
+
+    // power := primary ('^' unary)?
+    // The exponent is parsed as a unary so that "-x^2" is -(x^2) and "2^-x" is accepted.
+    fn power(&mut self) -> Result<Expr, ParseError> {
+        let base = self.primary()?;
+        if self.peek().kind == TokenKind::Caret {
+            self.next();
+            Ok(Expr::pow(base, self.unary()?))
+        } else {
+            Ok(base)
+        }
+    }
+
+    // primary := number | identifier | identifier '(' sum (',' sum)* ')' | '(' sum ')'
+    fn primary(&mut self) -> Result<Expr, ParseError> {
+        let token = self.next();
+        match token.kind {
+            TokenKind::Number(value) => Ok(Expr::Constant(value)),
+            TokenKind::Ident => self.identifier(token),
+            TokenKind::LParen => {
+                let inner = self.sum()?;
+                let close = self.next();
+                if close.kind == TokenKind::RParen {
+                    Ok(inner)
+                } else if close.kind == TokenKind::End {
+                    Err(ParseError::new("unclosed parenthesis", token.span))
+                } else {
+                    Err(self.unexpected(&close, "')'"))
+                }
+            }
+            _ => Err(self.unexpected(&token, "an expression")),
+        }
+    }
+
+    fn identifier(&mut self, token: Token) -> Result<Expr, ParseError> {
+        let name = &self.source[token.span.clone()];
+        if let Some(func) = Func::from_name(name) {
+            return self.call(func, token);
+        }
+        match name {
+            "pi" => Ok(Expr::Constant(std::f64::consts::PI)),
+            "tau" => Ok(Expr::Constant(std::f64::consts::TAU)),
+            "e" => Ok(Expr::Constant(std::f64::consts::E)),
+            "i" if self.complex => Ok(Expr::ImaginaryUnit),
+            "i" => Err(ParseError::new("the imaginary unit 'i' can only be used in complex plots", token.span)),
+            _ => Ok(Expr::variable(name)),
+        }
+    }
+
+    fn call(&mut self, func: Func, name: Token) -> Result<Expr, ParseError> {
+        let open = self.next();
+        if open.kind != TokenKind::LParen {
+            return Err(ParseError::new(format!("function '{}' must be followed by '('", func.name()), name.span));
+        }
+
+        let mut args = vec![self.sum()?];
+        let close = loop {
+            let token = self.next();
+            match token.kind {
+                TokenKind::Comma => args.push(self.sum()?),
+                TokenKind::RParen => break token,
+                TokenKind::End => return Err(ParseError::new("unclosed parenthesis", open.span)),
+                _ => return Err(self.unexpected(&token, "',' or ')'")),
+            }
+        };
+
+        if args.len() != func.arity() {
+            let plural = if func.arity() == 1 { "" } else { "s" };
+            let message = format!("function '{}' takes {} argument{}, found {}", func.name(), func.arity(), plural, args.len());
+            return Err(ParseError::new(message, name.span.start..close.span.end));
+        }
+
+        Ok(Expr::call(func, args))
+    }
+}
+
+impl Expr {
+    pub fn parse(source: &str) -> Result<Expr, ParseError> {
+        Expr::parse_with(source, false)
+    }
+
+    // Like parse, with "i" as the imaginary unit.
+    pub fn parse_complex(source: &str) -> Result<Expr, ParseError> {
+        Expr::parse_with(source, true)
+    }
+
+    fn parse_with(source: &str, complex: bool) -> Result<Expr, ParseError> {
+        let mut parser = Parser { source, tokens: tokenize(source)?, position: 0, complex };
+        let expr = parser.sum()?;
+        let token = parser.next();
+        match token.kind {
+            TokenKind::End => Ok(expr),
+            TokenKind::RParen => Err(ParseError::new("unmatched ')'", token.span)),
+            _ => Err(parser.unexpected(&token, "an operator")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parses_as(source: &str, expected: &str) {
+        assert_eq!(Expr::parse(source).unwrap(), Expr::parse(expected).unwrap(), "{}", source);
+    }
+
+    fn fails_at(source: &str, message: &str, span: Range<usize>) {
+        let error = Expr::parse(source).unwrap_err();
+        assert_eq!((error.message.as_str(), error.span), (message, span), "{}", source);
+    }
+
+    #[test]
+    fn implicit_multiplication() {
+        parses_as("2x", "2 * x");
+        parses_as("3(x + 1)", "3 * (x + 1)");
+        parses_as("x(28 - z)", "x * (28 - z)");
+        parses_as("2x^2", "2 * (x^2)");
+        parses_as("2 sin(x) cos(y)", "(2 * sin(x)) * cos(y)");
+        parses_as("2e", "2 * e");
+        assert_eq!(Expr::parse("2e3").unwrap(), Expr::Constant(2000.0));
+    }
+
+    #[test]
+    fn precedence() {
+        let x = || Expr::variable("x");
+        assert_eq!(Expr::parse("-x^2").unwrap(), -Expr::pow(x(), Expr::Constant(2.0)));
+        assert_eq!(Expr::parse("2^-x").unwrap(), Expr::pow(Expr::Constant(2.0), -x()));
+        parses_as("a + b * c", "a + (b * c)");
+        parses_as("a - b - c", "(a - b) - c");
+        parses_as("a / b / c", "(a / b) / c");
+        parses_as("a / b c", "(a / b) * c");
+        parses_as("2^3^x", "2^(3^x)");
+        parses_as("--x", "-(-x)");
+        parses_as("+x", "x");
+    }
+
+    #[test]
+    fn error_spans() {
+        fails_at("1 +", "expected an expression, found end of input", 3..3);
+        fails_at("(x + 1", "unclosed parenthesis", 0..1);
+        fails_at("sin(x", "unclosed parenthesis", 3..4);
+        fails_at("x + 1)", "unmatched ')'", 5..6);
+        fails_at("(x 1)", "expected ')', found '1'", 3..4);
+        fails_at("x # y", "unexpected character '#'", 2..3);
+        fails_at("x + é", "unexpected character 'é'", 4..6);
+        fails_at("1.2.3", "invalid number '1.2.3'", 0..5);
+        fails_at("sin x", "function 'sin' must be followed by '('", 0..3);
+        fails_at("1 + sin(x, y)", "function 'sin' takes 1 argument, found 2", 4..13);
+    }
+
+    #[test]
+    fn imaginary_unit_only_in_complex_expressions() {
+        let error = Expr::parse("x + i").unwrap_err();
+        assert_eq!(error.span, 4..5);
+        assert_eq!(Expr::parse_complex("x + i").unwrap(), Expr::add(Expr::variable("x"), Expr::ImaginaryUnit));
+    }
+}
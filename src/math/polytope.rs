@@ -0,0 +1,298 @@
+use std::collections::{HashMap, HashSet};
+
+use super::{
+    nd::{gram_schmidt, Vector},
+    wythoff::CoxeterDiagram,
+};
+
+// A convex polytope given by its vertices, edges and 2-faces. Faces list their vertices in order
+// around the polygon.
+#[derive(Clone, Debug)]
+pub struct Polytope {
+    pub vertices: Vec<Vector>,
+    pub edges: Vec<(usize, usize)>,
+    pub faces: Vec<Vec<usize>>,
+}
+
+const PHI: f64 = 1.618033988749895;
+
+// Connecting the vertices compares every pair, so a 20-cube would already take minutes.
+const MAX_DIMENSIONS: usize = 10;
+
+impl Polytope {
+    // Parses names like "4-cube", "5-simplex", "3-orthoplex", "tesseract", "24-cell", "120-cell" and
+    // "600-cell", and Coxeter diagrams or Schläfli symbols for Wythoff's construction.
+    pub fn from_name(name: &str) -> Result<Polytope, String> {
+        match name {
+            "tesseract" => return Ok(Polytope::cube(4)),
+            "5-cell" => return Ok(Polytope::simplex(4)),
+            "16-cell" => return Ok(Polytope::orthoplex(4)),
+            "24-cell" => return Ok(Polytope::cell24()),
+            "120-cell" => return Ok(Polytope::cell120()),
+            "600-cell" => return Ok(Polytope::cell600()),
+            _ => {}
+        }
+        if name.starts_with(['{', 't', 'x', 'o']) {
+            return Polytope::wythoff(&CoxeterDiagram::parse(name)?);
+        }
+
+        let unknown = || format!("unknown polytope '{}', expected something like 4-cube, 24-cell, {{4,3,3}} or x4x3o3o", name);
+        let (dimensions, family) = name.split_once('-').ok_or_else(unknown)?;
+        let dimensions: usize = dimensions.parse().map_err(|_| unknown())?;
+        if dimensions < 2 {
+            return Err(format!("polytopes need at least 2 dimensions, got {}", dimensions));
+        }
+        if dimensions > MAX_DIMENSIONS {
+            return Err(format!("polytopes can have at most {} dimensions, got {}", MAX_DIMENSIONS, dimensions));
+        }
+        match family {
+            "cube" => Ok(Polytope::cube(dimensions)),
+            "simplex" => Ok(Polytope::simplex(dimensions)),
+            "orthoplex" => Ok(Polytope::orthoplex(dimensions)),
+            _ => Err(unknown()),
+        }
+    }
+
+    pub fn dimensions(&self) -> usize {
+        self.vertices.first().map_or(0, Vector::dimensions)
+    }
+
+    pub fn cube(n: usize) -> Polytope {
+        let vertices = (0..1usize << n).map(|bits| Vector::from((0..n).map(|i| if (bits >> i) & 1 == 1 { 1.0 } else { -1.0 }).collect::<Vec<_>>())).collect();
+        Polytope::from_vertices(vertices, 4)
+    }
+
+    // The unit vectors e_0 .. e_(n-1) together with (a, .., a), which is at the same distance from
+    // all of them when a = (1 - sqrt(n + 1)) / n, moved so that their centroid is the origin.
+    pub fn simplex(n: usize) -> Polytope {
+        let a = (1.0 - ((n + 1) as f64).sqrt()) / n as f64;
+        let centroid = Vector::from(vec![(1.0 + a) / (n + 1) as f64; n]);
+        let mut vertices: Vec<Vector> = (0..n).map(|i| &Vector::axis(n, i) - &centroid).collect();
+        vertices.push(&Vector::from(vec![a; n]) - &centroid);
+        Polytope::from_vertices(vertices, 3)
+    }
+
+    pub fn orthoplex(n: usize) -> Polytope {
+        let vertices = (0..n).flat_map(|i| [Vector::axis(n, i), -&Vector::axis(n, i)]).collect();
+        // In two dimensions the orthoplex is a square rather than a union of triangles.
+        Polytope::from_vertices(vertices, if n == 2 { 4 } else { 3 })
+    }
+
+    pub fn cell24() -> Polytope {
+        Polytope::from_vertices(signed_permutations(&[1.0, 1.0, 0.0, 0.0], false), 3)
+    }
+
+    pub fn cell600() -> Polytope {
+        let mut vertices = signed_permutations(&[0.5, 0.5, 0.5, 0.5], false);
+        vertices.extend(signed_permutations(&[1.0, 0.0, 0.0, 0.0], false));
+        vertices.extend(signed_permutations(&[PHI / 2.0, 0.5, 1.0 / (2.0 * PHI), 0.0], true));
+        Polytope::from_vertices(vertices, 3)
+    }
+
+    pub fn cell120() -> Polytope {
+        let (phi, sqrt5) = (PHI, 5f64.sqrt());
+        let mut vertices = signed_permutations(&[0.0, 0.0, 2.0, 2.0], false);
+        vertices.extend(signed_permutations(&[1.0, 1.0, 1.0, sqrt5], false));
+        vertices.extend(signed_permutations(&[phi.powi(-2), phi, phi, phi], false));
+        vertices.extend(signed_permutations(&[1.0 / phi, 1.0 / phi, 1.0 / phi, phi * phi], false));
+        vertices.extend(signed_permutations(&[0.0, phi.powi(-2), 1.0, phi * phi], true));
+        vertices.extend(signed_permutations(&[0.0, 1.0 / phi, phi, sqrt5], true));
+        vertices.extend(signed_permutations(&[1.0 / phi, 1.0, phi, 2.0], true));
+        Polytope::from_vertices(vertices, 5)
+    }
+
+    // The 3-faces of a 4D polytope, as lists of vertices. Each one lies in the hyperplane through two
+    // 2-faces that share an edge, and has every other vertex on the same side.
+    pub fn cells(&self) -> Vec<Vec<usize>> {
+        assert_eq!(self.dimensions(), 4, "cells can only be found for 4D polytopes");
+
+        let mut faces_by_edge: HashMap<(usize, usize), Vec<usize>> = HashMap::new();
+        for (f, face) in self.faces.iter().enumerate() {
+            for k in 0..face.len() {
+                let (a, b) = (face[k], face[(k + 1) % face.len()]);
+                faces_by_edge.entry((a.min(b), a.max(b))).or_default().push(f);
+            }
+        }
+
+        let center = &self.vertices.iter().fold(Vector::zeros(4), |sum, v| &sum + v) * (1.0 / self.vertices.len() as f64);
+        let mut tried = HashSet::new();
+        let mut cells = Vec::new();
+        for faces in faces_by_edge.values() {
+            for (n, &f) in faces.iter().enumerate() {
+                for &g in &faces[n + 1..] {
+                    let (f, g) = (&self.faces[f], &self.faces[g]);
+                    let origin = &self.vertices[f[0]];
+                    let Some(&other) = g.iter().find(|v| !f.contains(v)) else {
+                        continue;
+                    };
+                    let directions = [&self.vertices[f[1]] - origin, &self.vertices[f[2]] - origin, &self.vertices[other] - origin];
+                    if gram_schmidt(&directions).len() < 3 {
+                        continue;
+                    }
+
+                    // Completing the three directions to a basis leaves the normal as the fourth vector.
+                    let mut basis = directions.to_vec();
+                    basis.extend((0..4).map(|axis| Vector::axis(4, axis)));
+                    let mut normal = gram_schmidt(&basis).swap_remove(3);
+                    if normal.dot(&(&center - origin)) > 0.0 {
+                        normal = -&normal;
+                    }
+                    let offset = normal.dot(origin);
+
+                    let key: Vec<i64> = normal.as_slice().iter().chain([&offset]).map(|x| (x * 1e6).round() as i64).collect();
+                    if !tried.insert(key) {
+                        continue;
+                    }
+                    if self.vertices.iter().any(|v| normal.dot(v) > offset + 1e-9) {
+                        continue;
+                    }
+                    cells.push((0..self.vertices.len()).filter(|&v| normal.dot(&self.vertices[v]) > offset - 1e-9).collect());
+                }
+            }
+        }
+        cells
+    }
+
+    // Scales the vertices to unit circumradius and connects them. For the regular polytopes the edges
+    // are exactly the shortest distances between vertices, and the 2-faces are the cycles of
+    // `face_size` edges.
+    fn from_vertices(vertices: Vec<Vector>, face_size: usize) -> Polytope {
+        let radius = vertices[0].norm();
+        let vertices: Vec<Vector> = vertices.iter().map(|v| v * (1.0 / radius)).collect();
+
+        let distance = |i: usize, j: usize| (&vertices[i] - &vertices[j]).norm();
+        let shortest = (0..vertices.len()).flat_map(|i| (i + 1..vertices.len()).map(move |j| (i, j))).map(|(i, j)| distance(i, j)).fold(f64::INFINITY, f64::min);
+        let mut edges = Vec::new();
+        for i in 0..vertices.len() {
+            for j in i + 1..vertices.len() {
+                if distance(i, j) < shortest * (1.0 + 1e-6) {
+                    edges.push((i, j));
+                }
+            }
+        }
+
+        let faces = cycles(vertices.len(), &edges, face_size);
+        Polytope { vertices, edges, faces }
+    }
+}
+
+// Every arrangement of `base` with every combination of signs, skipping duplicates. With `even`, only
+// the even permutations are used.
+fn signed_permutations(base: &[f64], even: bool) -> Vec<Vector> {
+    let n = base.len();
+    let mut orders = Vec::new();
+    permutations(&mut (0..n).collect(), 0, true, &mut orders);
+
+    let mut vertices: Vec<Vector> = Vec::new();
+    for (order, is_even) in orders {
+        if even && !is_even {
+            continue;
+        }
+        for signs in 0..1usize << n {
+            let vertex = Vector::from((0..n).map(|i| if (signs >> i) & 1 == 1 { -base[order[i]] } else { base[order[i]] }).collect::<Vec<_>>());
+            if !vertices.iter().any(|v| (v - &vertex).norm() < 1e-9) {
+                vertices.push(vertex);
+            }
+        }
+    }
+    vertices
+}
+
+// Generates the permutations by swapping, tracking whether each one is even.
+fn permutations(order: &mut Vec<usize>, start: usize, is_even: bool, out: &mut Vec<(Vec<usize>, bool)>) {
+    if start == order.len() {
+        out.push((order.clone(), is_even));
+        return;
+    }
+    for i in start..order.len() {
+        order.swap(start, i);
+        permutations(order, start + 1, is_even == (i == start), out);
+        order.swap(start, i);
+    }
+}
+
+// All cycles of exactly `length` vertices, each listed once starting from its smallest vertex.
+fn cycles(vertex_count: usize, edges: &[(usize, usize)], length: usize) -> Vec<Vec<usize>> {
+    let mut neighbours = vec![Vec::new(); vertex_count];
+    for &(a, b) in edges {
+        neighbours[a].push(b);
+        neighbours[b].push(a);
+    }
+
+    fn extend(path: &mut Vec<usize>, neighbours: &[Vec<usize>], length: usize, out: &mut Vec<Vec<usize>>) {
+        let (first, last) = (path[0], *path.last().unwrap());
+        if path.len() == length {
+            // Each cycle is found in both directions, keep the one where the second vertex is smaller.
+            if neighbours[last].contains(&first) && path[1] < last {
+                out.push(path.clone());
+            }
+            return;
+        }
+        for &next in &neighbours[last] {
+            if next > first && !path.contains(&next) {
+                path.push(next);
+                extend(path, neighbours, length, out);
+                path.pop();
+            }
+        }
+    }
+
+    let mut out = Vec::new();
+    for start in 0..vertex_count {
+        extend(&mut vec![start], &neighbours, length, &mut out);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn counts(name: &str) -> [usize; 4] {
+        let polytope = Polytope::from_name(name).unwrap();
+        let cells = if polytope.dimensions() == 4 { polytope.cells().len() } else { 0 };
+        [polytope.vertices.len(), polytope.edges.len(), polytope.faces.len(), cells]
+    }
+
+    #[test]
+    fn regular_polytope_counts() {
+        assert_eq!(counts("3-cube"), [8, 12, 6, 0]);
+        assert_eq!(counts("3-simplex"), [4, 6, 4, 0]);
+        assert_eq!(counts("3-orthoplex"), [6, 12, 8, 0]);
+        assert_eq!(counts("2-orthoplex"), [4, 4, 1, 0]);
+        assert_eq!(counts("tesseract"), [16, 32, 24, 8]);
+        assert_eq!(counts("5-cell"), [5, 10, 10, 5]);
+        assert_eq!(counts("16-cell"), [8, 24, 32, 16]);
+        assert_eq!(counts("24-cell"), [24, 96, 96, 24]);
+        assert_eq!(counts("600-cell"), [120, 720, 1200, 600]);
+        assert_eq!(counts("120-cell"), [600, 1200, 720, 120]);
+        assert_eq!(counts("5-cube"), [32, 80, 80, 0]);
+    }
+
+    #[test]
+    fn vertices_lie_on_the_unit_sphere() {
+        for name in ["4-simplex", "24-cell", "120-cell"] {
+            assert!(Polytope::from_name(name).unwrap().vertices.iter().all(|v| (v.norm() - 1.0).abs() < 1e-9), "{}", name);
+        }
+    }
+
+    #[test]
+    fn faces_are_cycles_of_edges() {
+        let polytope = Polytope::from_name("24-cell").unwrap();
+        for face in &polytope.faces {
+            for k in 0..face.len() {
+                let (a, b) = (face[k], face[(k + 1) % face.len()]);
+                assert!(polytope.edges.contains(&(a.min(b), a.max(b))));
+            }
+        }
+    }
+
+    #[test]
+    fn unknown_names() {
+        assert!(Polytope::from_name("4-blob").unwrap_err().starts_with("unknown polytope '4-blob'"));
+        assert!(Polytope::from_name("cube").is_err());
+        assert_eq!(Polytope::from_name("1-cube").unwrap_err(), "polytopes need at least 2 dimensions, got 1");
+        assert_eq!(Polytope::from_name("64-cube").unwrap_err(), "polytopes can have at most 10 dimensions, got 64");
+        assert!(Polytope::from_name("1000-simplex").is_err());
+    }
+}
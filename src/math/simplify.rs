@@ -0,0 +1,372 @@
+use std::cmp::Ordering;
+
+use super::expr::Expr;
+use enum_ordinalize::Ordinalize;
+
+impl Expr {
+    pub fn simplify(&self) -> Expr {
+        match self {
+            Expr::Constant(_) | Expr::ImaginaryUnit | Expr::Variable(_) => self.clone(),
+            Expr::Neg(_) | Expr::Add(..) | Expr::Sub(..) => {
+                let mut terms = Vec::new();
+                collect_terms(self, 1.0, &mut terms, true);
+                build_sum(terms)
+            }
+            Expr::Mul(..) | Expr::Div(..) => {
+                let mut product = Product { coefficient: 1.0, factors: Vec::new() };
+                product.collect(self, false, true);
+                product.build()
+            }
+            Expr::Pow(base, exponent) => simplify_pow(base.simplify(), exponent.simplify()),
+            Expr::Call(func, args) => {
+                let args: Vec<Expr> = args.iter().map(Expr::simplify).collect();
+                let constant = args.iter().all(|arg| matches!(arg, Expr::Constant(_)));
+                let call = Expr::call(*func, args);
+                // Constants are only folded where the real result is finite. Elsewhere, like sqrt(-1),
+                // the call is kept for complex plots to evaluate.
+                match call.eval() {
+                    value if constant && value.is_finite() => Expr::Constant(value),
+                    _ => call,
+                }
+            }
+        }
+    }
+}
+
+// Flattens nested sums into (coefficient, term) pairs, where the term carries no numeric factor.
+// Constants are stored with a term of 1. Operands are simplified once on the way down, so that
+// already simplified subtrees are only taken apart and never simplified again.
+fn collect_terms(expr: &Expr, sign: f64, terms: &mut Vec<(f64, Expr)>, simplify: bool) {
+    match expr {
+        Expr::Neg(operand) => collect_terms(operand, -sign, terms, simplify),
+        Expr::Add(lhs, rhs) => {
+            collect_terms(lhs, sign, terms, simplify);
+            collect_terms(rhs, sign, terms, simplify);
+        }
+        Expr::Sub(lhs, rhs) => {
+            collect_terms(lhs, sign, terms, simplify);
+            collect_terms(rhs, -sign, terms, simplify);
+        }
+        _ if simplify => collect_terms(&expr.simplify(), sign, terms, false),
+        &Expr::Constant(value) => add_term(terms, sign * value, Expr::Constant(1.0)),
+        _ => {
+            let (coefficient, term) = split_coefficient(expr.clone());
+            add_term(terms, sign * coefficient, term);
+        }
+    }
+}
+
+fn add_term(terms: &mut Vec<(f64, Expr)>, coefficient: f64, term: Expr) {
+    match terms.iter_mut().find(|(_, existing)| *existing == term) {
+        Some((existing, _)) => *existing += coefficient,
+        None => terms.push((coefficient, term)),
+    }
+}
+
+// Splits the numeric factor off a simplified product, matching the shapes built by `Product::build`.
+fn split_coefficient(expr: Expr) -> (f64, Expr) {
+    match expr {
+        Expr::Constant(value) => (value, Expr::Constant(1.0)),
+        Expr::Neg(operand) => {
+            let (coefficient, term) = split_coefficient(*operand);
+            (-coefficient, term)
+        }
+        Expr::Mul(lhs, rhs) => match *lhs {
+            Expr::Constant(value) => (value, *rhs),
+            lhs => {
+                let (coefficient, lhs) = split_coefficient(lhs);
+                (coefficient, Expr::mul(lhs, *rhs))
+            }
+        },
+        Expr::Div(lhs, rhs) => {
+            let (coefficient, lhs) = split_coefficient(*lhs);
+            (coefficient, Expr::div(lhs, *rhs))
+        }
+        expr => (1.0, expr),
+    }
+}
+
+fn build_sum(mut terms: Vec<(f64, Expr)>) -> Expr {
+    terms.retain(|&(coefficient, _)| coefficient != 0.0);
+    terms.sort_by(|(_, a), (_, b)| compare_terms(a, b));
+
+    let mut sum: Option<Expr> = None;
+    for (coefficient, term) in terms {
+        let magnitude = scale(coefficient.abs(), term);
+        sum = Some(match sum {
+            None if coefficient < 0.0 => negate(magnitude),
+            None => magnitude,
+            Some(sum) if coefficient < 0.0 => Expr::sub(sum, magnitude),
+            Some(sum) => Expr::add(sum, magnitude),
+        });
+    }
+    sum.unwrap_or(Expr::Constant(0.0))
+}
+
+fn scale(coefficient: f64, term: Expr) -> Expr {
+    match term {
+        Expr::Constant(value) => Expr::Constant(coefficient * value),
+        term if coefficient == 1.0 => term,
+        Expr::Div(lhs, rhs) => Expr::div(scale(coefficient, *lhs), *rhs),
+        term => Expr::mul(Expr::Constant(coefficient), term),
+    }
+}
+
+fn negate(expr: Expr) -> Expr {
+    match expr {
+        Expr::Constant(value) => Expr::Constant(-value),
+        Expr::Mul(lhs, rhs) => Expr::mul(negate(*lhs), *rhs),
+        Expr::Div(lhs, rhs) => Expr::div(negate(*lhs), *rhs),
+        expr => -expr,
+    }
+}
+
+struct Product {
+    coefficient: f64,
+    factors: Vec<(Expr, Expr)>,
+}
+
+impl Product {
+    fn collect(&mut self, expr: &Expr, inverted: bool, simplify: bool) {
+        match expr {
+            Expr::Mul(lhs, rhs) => {
+                self.collect(lhs, inverted, simplify);
+                self.collect(rhs, inverted, simplify);
+            }
+            Expr::Div(lhs, rhs) => {
+                self.collect(lhs, inverted, simplify);
+                self.collect(rhs, !inverted, simplify);
+            }
+            Expr::Neg(operand) => {
+                self.coefficient = -self.coefficient;
+                self.collect(operand, inverted, simplify);
+            }
+            _ if simplify => self.collect(&expr.simplify(), inverted, false),
+            &Expr::Constant(value) if inverted => self.coefficient /= value,
+            &Expr::Constant(value) => self.coefficient *= value,
+            Expr::Pow(base, exponent) => {
+                let exponent = if inverted { negate((**exponent).clone()) } else { (**exponent).clone() };
+                match exponent {
+                    // (a b)^n = a^n b^n, which only holds in general for integer n. Distributing the power
+                    // lets factors inside and outside of it cancel, as in 2y / (2y)^2.
+                    Expr::Constant(n) if n.fract() == 0.0 && matches!(**base, Expr::Mul(..) | Expr::Div(..) | Expr::Neg(_)) => {
+                        let mut inner = Product { coefficient: 1.0, factors: Vec::new() };
+                        inner.collect(base, false, false);
+                        self.coefficient *= inner.coefficient.powf(n);
+                        for (base, exponent) in inner.factors {
+                            self.add_factor(base, Expr::mul(exponent, Expr::Constant(n)).simplify());
+                        }
+                    }
+                    exponent => self.add_factor((**base).clone(), exponent),
+                }
+            }
+            _ => self.add_factor(expr.clone(), Expr::Constant(if inverted { -1.0 } else { 1.0 })),
+        }
+    }
+
+    fn add_factor(&mut self, base: Expr, exponent: Expr) {
+        match self.factors.iter_mut().find(|(existing, _)| *existing == base) {
+            Some((_, existing)) => *existing = Expr::add(existing.clone(), exponent).simplify(),
+            None => self.factors.push((base, exponent)),
+        }
+    }
+
+    fn build(mut self) -> Expr {
+        if self.coefficient == 0.0 {
+            return Expr::Constant(0.0);
+        }
+
+        // Integer powers of i reduce to one of 1, i, -1 and -i.
+        for (base, exponent) in &mut self.factors {
+            if let (Expr::ImaginaryUnit, &mut Expr::Constant(n)) = (&*base, &mut *exponent) {
+                if n.fract() == 0.0 {
+                    let n = n.rem_euclid(4.0);
+                    if n >= 2.0 {
+                        self.coefficient = -self.coefficient;
+                    }
+                    *exponent = Expr::Constant(n % 2.0);
+                }
+            }
+        }
+
+        self.factors.sort_by(|(a, _), (b, _)| compare(a, b));
+
+        let mut numerator: Option<Expr> = None;
+        let mut denominator: Option<Expr> = None;
+        for (base, exponent) in self.factors {
+            let (part, exponent) = match exponent {
+                Expr::Constant(value) if value < 0.0 => (&mut denominator, Expr::Constant(-value)),
+                exponent => (&mut numerator, exponent),
+            };
+            let factor = simplify_pow(base, exponent);
+            if factor != Expr::Constant(1.0) {
+                *part = Some(match part.take() {
+                    Some(product) => Expr::mul(product, factor),
+                    None => factor,
+                });
+            }
+        }
+
+        let numerator = match numerator {
+            Some(numerator) if self.coefficient == -1.0 => negate(numerator),
+            Some(numerator) if self.coefficient == 1.0 => numerator,
+            Some(numerator) => Expr::mul(Expr::Constant(self.coefficient), numerator),
+            None => Expr::Constant(self.coefficient),
+        };
+        match denominator {
+            Some(denominator) => Expr::div(numerator, denominator),
+            None => numerator,
+        }
+    }
+}
+
+fn simplify_pow(base: Expr, exponent: Expr) -> Expr {
+    match (base, exponent) {
+        (Expr::Constant(base), Expr::Constant(exponent)) if base.powf(exponent).is_finite() => Expr::Constant(base.powf(exponent)),
+        (_, Expr::Constant(0.0)) => Expr::Constant(1.0),
+        (base, Expr::Constant(1.0)) => base,
+        (Expr::Constant(1.0), _) => Expr::Constant(1.0),
+        (Expr::ImaginaryUnit, Expr::Constant(exponent)) if exponent.fract() == 0.0 => match exponent.rem_euclid(4.0) {
+            0.0 => Expr::Constant(1.0),
+            1.0 => Expr::ImaginaryUnit,
+            2.0 => Expr::Constant(-1.0),
+            _ => -Expr::ImaginaryUnit,
+        },
+        // (b^p)^q = b^(p * q) only holds in general for integer q.
+        (Expr::Pow(base, inner), Expr::Constant(outer)) if outer.fract() == 0.0 => {
+            simplify_pow(*base, Expr::mul(*inner, Expr::Constant(outer)).simplify())
+        }
+        (base @ (Expr::Mul(..) | Expr::Div(..) | Expr::Neg(_)), Expr::Constant(n)) if n.fract() == 0.0 => {
+            let mut product = Product { coefficient: 1.0, factors: Vec::new() };
+            product.collect(&Expr::pow(base, Expr::Constant(n)), false, false);
+            product.build()
+        }
+        (base, exponent) => Expr::pow(base, exponent),
+    }
+}
+
+// Polynomial degree, used to print sums with the highest powers first.
+fn degree(expr: &Expr) -> f64 {
+    match expr {
+        Expr::Constant(_) | Expr::ImaginaryUnit => 0.0,
+        Expr::Pow(base, exponent) => match **exponent {
+            Expr::Constant(exponent) => degree(base) * exponent,
+            _ => 1.0,
+        },
+        Expr::Mul(lhs, rhs) => degree(lhs) + degree(rhs),
+        Expr::Div(lhs, rhs) => degree(lhs) - degree(rhs),
+        Expr::Neg(operand) => degree(operand),
+        _ => 1.0,
+    }
+}
+
+fn compare_terms(a: &Expr, b: &Expr) -> Ordering {
+    degree(b).total_cmp(&degree(a)).then_with(|| compare(a, b))
+}
+
+// A total order on expressions, used to give commutative operands a canonical arrangement.
+fn compare(a: &Expr, b: &Expr) -> Ordering {
+    fn rank(expr: &Expr) -> u8 {
+        match expr {
+            Expr::Constant(_) => 0,
+            Expr::ImaginaryUnit => 1,
+            Expr::Variable(_) => 2,
+            Expr::Call(..) => 3,
+            Expr::Pow(..) => 4,
+            Expr::Mul(..) => 5,
+            Expr::Div(..) => 6,
+            Expr::Add(..) => 7,
+            Expr::Sub(..) => 8,
+            Expr::Neg(_) => 9,
+        }
+    }
+
+    match (a, b) {
+        (Expr::Constant(a), Expr::Constant(b)) => a.total_cmp(b),
+        (Expr::Variable(a), Expr::Variable(b)) => a.cmp(b),
+        (Expr::Neg(a), Expr::Neg(b)) => compare(a, b),
+        (Expr::Call(a, a_args), Expr::Call(b, b_args)) => a.ordinal().cmp(&b.ordinal()).then_with(|| compare_all(a_args, b_args)),
+        (Expr::Add(a0, a1), Expr::Add(b0, b1))
+        | (Expr::Sub(a0, a1), Expr::Sub(b0, b1))
+        | (Expr::Mul(a0, a1), Expr::Mul(b0, b1))
+        | (Expr::Div(a0, a1), Expr::Div(b0, b1))
+        | (Expr::Pow(a0, a1), Expr::Pow(b0, b1)) => compare(a0, b0).then_with(|| compare(a1, b1)),
+        _ => rank(a).cmp(&rank(b)),
+    }
+}
+
+fn compare_all(a: &[Expr], b: &[Expr]) -> Ordering {
+    a.iter().zip(b).map(|(a, b)| compare(a, b)).find(|ordering| ordering.is_ne()).unwrap_or_else(|| a.len().cmp(&b.len()))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::math::{
+        complex::Complex,
+        expr::{Env, Expr},
+    };
+
+    // Simplifying may not change the value of a complex plot.
+    fn assert_same_complex_value(source: &str, z: Complex) {
+        let expr = Expr::parse_complex(source).unwrap();
+        let env = Env::new().with("z", z);
+        let (before, after) = (expr.eval_complex(&env), expr.simplify().eval_complex(&env));
+        assert!((before - after).abs() < 1e-12, "{}: {} before simplifying, {} after", source, before, after);
+    }
+
+    fn simplified(source: &str) -> Expr {
+        Expr::parse(source).unwrap().simplify()
+    }
+
+    #[test]
+    fn equal_forms_simplify_to_the_same_expression() {
+        for (a, b) in [
+            ("x + x", "2x"),
+            ("x - x + y", "y"),
+            ("x * x * x", "x^3"),
+            ("(x y)^2", "x^2 y^2"),
+            ("2y / (2y)^2", "1 / (2y)"),
+            ("x^2 y^3 / (x y)^2", "y"),
+            ("(x / y)^-2", "y^2 / x^2"),
+            ("((x y)^2)^3", "x^6 y^6"),
+            ("-(-x)", "x"),
+            ("0 * sin(x) + 1 * cos(x)^1", "cos(x)"),
+        ] {
+            assert_eq!(simplified(a), simplified(b), "{} and {}", a, b);
+        }
+    }
+
+    #[test]
+    fn derivatives_come_out_canonical() {
+        let derivative = Expr::parse("x / (2y)").unwrap().derivative("x").simplify();
+        assert_eq!(derivative, simplified("0.5 / y"));
+        assert_eq!(derivative.to_string(), "0.5 / y");
+    }
+
+    #[test]
+    fn simplifying_keeps_the_value() {
+        let env = Env::new().with("x", 0.7).with("y", -1.3);
+        for source in ["(2x)^0.5 + (x y)^3", "x / (3 y^2) - y / x^2", "sin(x y)^2 + cos(x y)^2", "(x - y)^2 - (y - x)^2", "2^x * 2^-x"] {
+            let expr = Expr::parse(source).unwrap();
+            let (before, after) = (expr.eval_with(&env), expr.simplify().eval_with(&env));
+            assert!((before - after).abs() < 1e-12 || (before.is_nan() && after.is_nan()), "{}: {} before simplifying, {} after", source, before, after);
+        }
+    }
+
+    #[test]
+    fn constants_without_a_real_value_are_not_folded() {
+        let z = Complex::new(0.5, -1.5);
+        for source in ["sqrt(-1) z", "ln(-2) + z", "(-8)^(1/3)", "asin(2) z", "z^2 + sqrt(-4)"] {
+            assert_same_complex_value(source, z);
+        }
+        assert_eq!(Expr::parse("sqrt(4) x").unwrap().simplify(), Expr::parse("2x").unwrap().simplify());
+    }
+
+    #[test]
+    fn negated_reals_take_principal_values() {
+        let env = Env::new();
+        let sqrt = Expr::parse_complex("sqrt(-1)").unwrap();
+        assert_eq!(sqrt.eval_complex(&env), Complex::I);
+        assert_eq!(sqrt.simplify().eval_complex(&env), Complex::I);
+    }
+}
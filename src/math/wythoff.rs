@@ -0,0 +1,334 @@
+use std::{
+    collections::{HashMap, HashSet},
+    f64::consts::PI,
+};
+
+use super::{nd::Vector, polytope::Polytope};
+
+// A linear Coxeter-Dynkin diagram: `orders[i]` is the branch between node i and node i + 1, and
+// ringed nodes are the mirrors the generating point lies off of.
+#[derive(Clone, PartialEq, Debug)]
+pub struct CoxeterDiagram {
+    pub orders: Vec<u32>,
+    pub ringed: Vec<bool>,
+}
+
+// Polytopes larger than this are refused rather than left to run out of memory.
+const MAX_VERTICES: usize = 100_000;
+
+impl CoxeterDiagram {
+    // Accepts ringed diagrams like "x4x3o3o", Schläfli symbols like "{4,3,3}", where only the first
+    // node is ringed, and truncation symbols like "t0,1{4,3,3}" listing the ringed nodes.
+    pub fn parse(source: &str) -> Result<CoxeterDiagram, String> {
+        let source = source.trim();
+        if let Some(rest) = source.strip_prefix('t') {
+            let (rings, symbol) = rest.split_once('{').ok_or_else(|| format!("expected a Schläfli symbol after '{}'", rest))?;
+            let orders = parse_schlafli(symbol)?;
+            let mut ringed = vec![false; orders.len() + 1];
+            for ring in rings.split(',') {
+                let node: usize = ring.trim().parse().map_err(|_| format!("invalid node '{}'", ring))?;
+                *ringed.get_mut(node).ok_or_else(|| format!("node {} is out of range, the diagram has {} nodes", node, orders.len() + 1))? = true;
+            }
+            return Ok(CoxeterDiagram { orders, ringed });
+        }
+        if let Some(symbol) = source.strip_prefix('{') {
+            let orders = parse_schlafli(symbol)?;
+            let mut ringed = vec![false; orders.len() + 1];
+            ringed[0] = true;
+            return Ok(CoxeterDiagram { orders, ringed });
+        }
+
+        let mut orders = Vec::new();
+        let mut ringed = Vec::new();
+        let mut chars = source.chars().peekable();
+        loop {
+            match chars.next() {
+                Some('x') => ringed.push(true),
+                Some('o') => ringed.push(false),
+                _ => return Err(format!("invalid Coxeter diagram '{}', expected something like x4x3o3o", source)),
+            }
+            if chars.peek().is_none() {
+                break;
+            }
+            let mut digits = String::new();
+            while let Some(&c) = chars.peek().filter(|c| c.is_ascii_digit()) {
+                digits.push(c);
+                chars.next();
+            }
+            orders.push(digits.parse().map_err(|_| format!("invalid Coxeter diagram '{}', expected a branch order after node {}", source, ringed.len() - 1))?);
+        }
+        Ok(CoxeterDiagram { orders, ringed })
+    }
+
+    pub fn dimensions(&self) -> usize {
+        self.ringed.len()
+    }
+
+    fn order(&self, i: usize, j: usize) -> u32 {
+        let (i, j) = (i.min(j), i.max(j));
+        if j == i + 1 { self.orders[i] } else { 2 }
+    }
+
+    // Unit normals of the mirrors, with n_i . n_j = -cos(pi / m_ij). They are the rows of the Cholesky
+    // factor of that Gram matrix, which only exists when the group is finite.
+    fn mirrors(&self) -> Result<Vec<Vector>, String> {
+        let n = self.dimensions();
+        let mut mirrors = vec![Vector::zeros(n); n];
+        for i in 0..n {
+            for j in 0..=i {
+                let gram = if i == j { 1.0 } else { -(PI / self.order(i, j) as f64).cos() };
+                let sum = gram - (0..j).map(|k| mirrors[i][k] * mirrors[j][k]).sum::<f64>();
+                if i == j {
+                    if sum <= 1e-9 {
+                        return Err("the diagram doesn't describe a finite reflection group".to_string());
+                    }
+                    mirrors[i][i] = sum.sqrt();
+                } else {
+                    mirrors[i][j] = sum / mirrors[j][j];
+                }
+            }
+        }
+        Ok(mirrors)
+    }
+}
+
+fn parse_schlafli(symbol: &str) -> Result<Vec<u32>, String> {
+    let inner = symbol.strip_suffix('}').ok_or_else(|| format!("unclosed Schläfli symbol '{{{}'", symbol))?;
+    inner.split(',').map(|order| order.trim().parse().map_err(|_| format!("invalid branch order '{}'", order))).collect()
+}
+
+fn reflect(point: &Vector, mirror: &Vector) -> Vector {
+    point - &(mirror * (2.0 * point.dot(mirror)))
+}
+
+// Identifies points by their coordinates rounded to a grid. A coordinate that lands close to a cell
+// boundary is also looked up in the neighbouring cell, so rounding errors can't split a vertex in two.
+struct PointSet {
+    points: Vec<Vector>,
+    ids: HashMap<Vec<i64>, usize>,
+}
+
+const GRID: f64 = 1e-6;
+
+impl PointSet {
+    fn keys(point: &Vector) -> Vec<Vec<i64>> {
+        let mut keys = vec![Vec::new()];
+        for &x in point.as_slice() {
+            let scaled = x / GRID;
+            let key = scaled.round() as i64;
+            let ambiguous = (scaled - scaled.floor() - 0.5).abs() < 0.05;
+            let alternative = if scaled.round() > scaled { key - 1 } else { key + 1 };
+            keys = keys
+                .into_iter()
+                .flat_map(|prefix| {
+                    let choices = if ambiguous { vec![key, alternative] } else { vec![key] };
+                    choices.into_iter().map(move |choice| {
+                        let mut key = prefix.clone();
+                        key.push(choice);
+                        key
+                    })
+                })
+                .collect();
+        }
+        keys
+    }
+
+    fn find(&self, point: &Vector) -> Option<usize> {
+        PointSet::keys(point).iter().find_map(|key| self.ids.get(key).copied())
+    }
+
+    fn insert(&mut self, point: Vector) {
+        if self.find(&point).is_none() {
+            self.ids.insert(PointSet::keys(&point).swap_remove(0), self.points.len());
+            self.points.push(point);
+        }
+    }
+}
+
+impl Polytope {
+    // Wythoff's construction: the vertices are the orbit of a point that lies on every unringed mirror
+    // and at unit distance from every ringed one. Edges are the orbits of the segments from that point
+    // to its reflections in ringed mirrors, and faces the orbits of its orbits under pairs of mirrors.
+    pub fn wythoff(diagram: &CoxeterDiagram) -> Result<Polytope, String> {
+        let n = diagram.dimensions();
+        if diagram.orders.len() + 1 != n {
+            let plural = if diagram.orders.len() == 1 { "" } else { "s" };
+            return Err(format!("a diagram with {} nodes has {} branches between them, got {} branch order{}", n, n.saturating_sub(1), diagram.orders.len(), plural));
+        }
+        if let Some(order) = diagram.orders.iter().find(|&&order| order < 2) {
+            return Err(format!("branch orders must be at least 2, got {}", order));
+        }
+        // A branch of order m alone makes polygons of up to 2m vertices.
+        if let Some(order) = diagram.orders.iter().find(|&&order| 2 * order as usize > MAX_VERTICES) {
+            return Err(format!("the branch order {} is too large, the polytope would have more than {} vertices", order, MAX_VERTICES));
+        }
+        if !diagram.ringed.contains(&true) {
+            return Err("at least one node has to be ringed".to_string());
+        }
+        let mirrors = diagram.mirrors()?;
+
+        // The mirrors are lower triangular, so the generating point follows by forward substitution.
+        let mut seed = Vector::zeros(n);
+        for i in 0..n {
+            let target = if diagram.ringed[i] { 1.0 } else { 0.0 };
+            seed[i] = (target - (0..i).map(|k| mirrors[i][k] * seed[k]).sum::<f64>()) / mirrors[i][i];
+        }
+        // Scaled to unit circumradius like the regular polytopes. Doing it before taking the orbit keeps
+        // the grid of the point set fine enough, also when nearly parallel mirrors put the point far out.
+        let seed = &seed * (1.0 / seed.norm());
+
+        let mut vertices = PointSet { points: Vec::new(), ids: HashMap::new() };
+        vertices.insert(seed.clone());
+        let mut next = 0;
+        while next < vertices.points.len() {
+            if vertices.points.len() > MAX_VERTICES {
+                return Err(format!("the polytope has more than {} vertices", MAX_VERTICES));
+            }
+            let point = vertices.points[next].clone();
+            for mirror in &mirrors {
+                vertices.insert(reflect(&point, mirror));
+            }
+            next += 1;
+        }
+        let id = |point: &Vector| vertices.find(point).expect("vertex orbit isn't closed");
+
+        let mut edges: Vec<Vec<usize>> = Vec::new();
+        for (i, mirror) in mirrors.iter().enumerate() {
+            if diagram.ringed[i] {
+                edges.push(vec![0, id(&reflect(&seed, mirror))]);
+            }
+        }
+        let edges = orbit(edges, &vertices.points, &mirrors, &id);
+
+        let mut faces = Vec::new();
+        for i in 0..n {
+            for j in i + 1..n {
+                if let Some(face) = polygon(&seed, &mirrors[i], &mirrors[j], diagram.order(i, j)) {
+                    faces.push(face.iter().map(&id).collect());
+                }
+            }
+        }
+        let faces = orbit(faces, &vertices.points, &mirrors, &id);
+
+        Ok(Polytope {
+            vertices: vertices.points,
+            edges: edges.into_iter().map(|edge| (edge[0], edge[1])).collect(),
+            faces,
+        })
+    }
+}
+
+// The orbit of `seed` under the dihedral group of two mirrors, in order around the polygon. None if it
+// degenerates to a point or a segment.
+fn polygon(seed: &Vector, a: &Vector, b: &Vector, order: u32) -> Option<Vec<Vector>> {
+    // Walking around the polygon alternately crosses the two mirrors: p, a p, a b p, a b a p, ... The
+    // points two apart differ by the rotation a b, so both halves are advanced one rotation at a time.
+    let rotate = |point: &Vector| reflect(&reflect(point, b), a);
+    let same = |p: &Vector, q: &Vector| (p - q).norm() < 1e-9;
+    let (mut even, mut odd) = (seed.clone(), reflect(seed, a));
+    let mut points: Vec<Vector> = vec![seed.clone()];
+    for k in 0..order {
+        if k > 0 {
+            even = rotate(&even);
+        }
+        for point in [&even, &odd] {
+            if !same(point, points.last().unwrap()) && !same(point, seed) {
+                points.push(point.clone());
+            }
+        }
+        odd = rotate(&odd);
+    }
+    if points.len() >= 3 { Some(points) } else { None }
+}
+
+// Closes a set of vertex lists under the reflections, treating lists with the same vertices as equal.
+fn orbit(seeds: Vec<Vec<usize>>, points: &[Vector], mirrors: &[Vector], id: &impl Fn(&Vector) -> usize) -> Vec<Vec<usize>> {
+    let key = |cell: &Vec<usize>| {
+        let mut key = cell.clone();
+        key.sort_unstable();
+        key
+    };
+
+    let mut seen = HashSet::new();
+    let mut cells = Vec::new();
+    for cell in seeds {
+        if seen.insert(key(&cell)) {
+            cells.push(cell);
+        }
+    }
+
+    let mut next = 0;
+    while next < cells.len() {
+        for mirror in mirrors {
+            let image: Vec<usize> = cells[next].iter().map(|&v| id(&reflect(&points[v], mirror))).collect();
+            if seen.insert(key(&image)) {
+                cells.push(image);
+            }
+        }
+        next += 1;
+    }
+    cells
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn counts(name: &str) -> [usize; 4] {
+        let polytope = Polytope::from_name(name).unwrap();
+        let cells = if polytope.dimensions() == 4 { polytope.cells().len() } else { 0 };
+        [polytope.vertices.len(), polytope.edges.len(), polytope.faces.len(), cells]
+    }
+
+    #[test]
+    fn parses_every_notation() {
+        let truncated = CoxeterDiagram { orders: vec![4, 3, 3], ringed: vec![true, true, false, false] };
+        assert_eq!(CoxeterDiagram::parse("x4x3o3o"), Ok(truncated.clone()));
+        assert_eq!(CoxeterDiagram::parse("t0,1{4,3,3}"), Ok(truncated));
+        assert_eq!(CoxeterDiagram::parse("{5,3}"), Ok(CoxeterDiagram { orders: vec![5, 3], ringed: vec![true, false, false] }));
+        assert_eq!(CoxeterDiagram::parse("o10x"), Ok(CoxeterDiagram { orders: vec![10], ringed: vec![false, true] }));
+    }
+
+    #[test]
+    fn polyhedron_counts() {
+        assert_eq!(counts("x4o3o"), [8, 12, 6, 0]);
+        assert_eq!(counts("{5,3}"), [20, 30, 12, 0]);
+        assert_eq!(counts("x4x3o"), [24, 36, 14, 0]);
+        assert_eq!(counts("x3x4x"), [48, 72, 26, 0]);
+        assert_eq!(counts("x5o3x"), [60, 120, 62, 0]);
+    }
+
+    #[test]
+    fn polychoron_counts() {
+        assert_eq!(counts("x4o3o3o"), [16, 32, 24, 8]);
+        assert_eq!(counts("t0,1{4,3,3}"), [64, 128, 88, 24]);
+        assert_eq!(counts("o3x3o3o"), [10, 30, 30, 10]);
+        assert_eq!(counts("o3o4o3x"), [24, 96, 96, 24]);
+        assert_eq!(counts("{3,3,5}"), [120, 720, 1200, 600]);
+    }
+
+    #[test]
+    fn large_branch_orders() {
+        assert_eq!(counts("x5000x"), [10000, 10000, 1, 0]);
+        assert_eq!(counts("x2000x2x"), [8000, 12000, 4002, 0]);
+    }
+
+    #[test]
+    fn vertices_lie_on_the_unit_sphere() {
+        let polytope = Polytope::from_name("x3x3o5o").unwrap();
+        assert!(polytope.vertices.iter().all(|v| (v.norm() - 1.0).abs() < 1e-9));
+    }
+
+    #[test]
+    fn invalid_diagrams() {
+        let error = |orders: Vec<u32>, ringed: Vec<bool>| Polytope::wythoff(&CoxeterDiagram { orders, ringed }).unwrap_err();
+        assert_eq!(error(vec![4], vec![true, false, false]), "a diagram with 3 nodes has 2 branches between them, got 1 branch order");
+        assert_eq!(error(vec![4, 3, 3], vec![true, false, false]), "a diagram with 3 nodes has 2 branches between them, got 3 branch orders");
+        assert_eq!(error(vec![60000], vec![true, true]), "the branch order 60000 is too large, the polytope would have more than 100000 vertices");
+        assert_eq!(error(vec![4, 1], vec![true, false, false]), "branch orders must be at least 2, got 1");
+        assert_eq!(error(vec![4, 3], vec![false; 3]), "at least one node has to be ringed");
+        assert_eq!(error(vec![6, 3], vec![true, false, false]), "the diagram doesn't describe a finite reflection group");
+        assert!(CoxeterDiagram::parse("x4y").is_err());
+        assert!(CoxeterDiagram::parse("t0,4{4,3,3}").unwrap_err().starts_with("node 4 is out of range"));
+    }
+}